@@ -0,0 +1,199 @@
+//! Game analysis: replays a game, searches every position and grades each
+//! move by the centipawns it lost compared to the engine's best move.
+
+use crate::pgn::read_games;
+use crate::search::{search, MATE_BOUND};
+use crate::{Colour, Game, Move};
+
+/// Centipawn loss is counted with scores clamped to this, so that missing a
+/// mate counts as a large loss rather than an enormous one.
+const SCORE_LIMIT: i32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Classification {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    /// Grade of a move that isn't the engine's best move.
+    pub fn from_loss(centipawn_loss: i32) -> Classification {
+        match centipawn_loss {
+            ..=50 => Classification::Good,
+            51..=100 => Classification::Inaccuracy,
+            101..=300 => Classification::Mistake,
+            _ => Classification::Blunder,
+        }
+    }
+}
+
+impl std::fmt::Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Classification::Best => "best",
+            Classification::Good => "good",
+            Classification::Inaccuracy => "inaccuracy",
+            Classification::Mistake => "mistake",
+            Classification::Blunder => "blunder",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveReport {
+    pub colour: Colour,
+    pub played: Move,
+    pub best: Option<Move>,
+    /// Score of the best move, from the point of view of the player who moved.
+    pub best_score: i32,
+    /// Score after the played move, from the point of view of the player who moved.
+    pub played_score: i32,
+    pub centipawn_loss: i32,
+    /// Between 0 and 100.
+    pub accuracy: f64,
+    pub classification: Classification,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalysisReport {
+    pub moves: Vec<MoveReport>,
+}
+
+impl AnalysisReport {
+    fn moves_by(&self, colour: Colour) -> impl Iterator<Item = &MoveReport> {
+        self.moves.iter().filter(move |report| report.colour == colour)
+    }
+
+    /// Average accuracy of `colour`'s moves between 0 and 100, or None if they made no moves.
+    pub fn accuracy(&self, colour: Colour) -> Option<f64> {
+        let count = self.moves_by(colour).count();
+        if count == 0 {
+            return None;
+        }
+        Some(self.moves_by(colour).map(|report| report.accuracy).sum::<f64>() / count as f64)
+    }
+
+    pub fn average_centipawn_loss(&self, colour: Colour) -> Option<f64> {
+        let count = self.moves_by(colour).count();
+        if count == 0 {
+            return None;
+        }
+        Some(self.moves_by(colour).map(|report| report.centipawn_loss as f64).sum::<f64>() / count as f64)
+    }
+
+    /// Number of `colour`'s moves with the given classification.
+    pub fn count(&self, colour: Colour, classification: Classification) -> usize {
+        self.moves_by(colour).filter(|report| report.classification == classification).count()
+    }
+}
+
+/// Chance of winning in percent for a score, as used by lichess.
+fn win_percent(score: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * score as f64).exp()) - 1.0)
+}
+
+/// Accuracy of a single move from the drop in winning chances, as used by lichess.
+fn move_accuracy(best_score: i32, played_score: i32) -> f64 {
+    let drop = (win_percent(best_score) - win_percent(played_score)).max(0.0);
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+fn clamp_score(score: i32) -> i32 {
+    if score > MATE_BOUND {
+        SCORE_LIMIT
+    }
+    else if score < -MATE_BOUND {
+        -SCORE_LIMIT
+    }
+    else {
+        score.clamp(-SCORE_LIMIT, SCORE_LIMIT)
+    }
+}
+
+/// Analyses the moves in `moves`, played from `start`, searching each position `depth` half moves deep.
+/// Returns None if one of the moves can't be played.
+pub fn analyse_moves(start: &Game, moves: &[Move], depth: u32) -> Option<AnalysisReport> {
+    let mut positions = vec![start.clone()];
+    for m in moves {
+        let mut next = positions[positions.len() - 1].clone();
+        next.play_move(m)?;
+        positions.push(next);
+    }
+
+    // Every position is searched once: the score after a move is the negated
+    // score of the next position
+    let results: Vec<_> = positions.iter().map(|position| search(position, depth)).collect();
+    let mut reports = vec![];
+    for (i, m) in moves.iter().enumerate() {
+        let best_score = clamp_score(results[i].score);
+        let played_score = clamp_score(-results[i + 1].score);
+        let centipawn_loss = (best_score - played_score).max(0);
+        let is_best = results[i].best_move.as_ref() == Some(m);
+        reports.push(MoveReport {
+            colour: positions[i].active_colour,
+            played: m.clone(),
+            best: results[i].best_move.clone(),
+            best_score,
+            played_score,
+            centipawn_loss: if is_best { 0 } else { centipawn_loss },
+            accuracy: if is_best { 100.0 } else { move_accuracy(best_score, played_score) },
+            classification: if is_best { Classification::Best } else { Classification::from_loss(centipawn_loss) },
+        });
+    }
+    Some(AnalysisReport { moves: reports })
+}
+
+/// Analyses every move played in `game`.
+pub fn analyse(game: &Game, depth: u32) -> Option<AnalysisReport> {
+    let start = Game::from_fen(&game.start_fen).ok()?;
+    analyse_moves(&start, &game.history, depth)
+}
+
+/// Analyses every game in a PGN collection, up to the first move of each that can't be played.
+pub fn analyse_pgn(text: &str, depth: u32) -> Vec<AnalysisReport> {
+    read_games(text).iter()
+        .filter_map(|pgn_game| analyse(&pgn_game.game, depth))
+        .collect()
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{analyse, analyse_pgn, Classification};
+    use crate::{Colour, Game};
+
+    #[test]
+    fn classify_loss() {
+        assert_eq!(Classification::from_loss(0), Classification::Good);
+        assert_eq!(Classification::from_loss(80), Classification::Inaccuracy);
+        assert_eq!(Classification::from_loss(200), Classification::Mistake);
+        assert_eq!(Classification::from_loss(900), Classification::Blunder);
+    }
+
+    #[test]
+    fn finds_queen_blunder() {
+        // 2. Qg4 puts the queen where the c8 bishop takes it
+        let reports = analyse_pgn("1. e4 d5 2. Qg4 Bxg4 *", 1);
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!(report.moves.len(), 4);
+        assert_eq!(report.moves[2].classification, Classification::Blunder);
+        assert_eq!(report.moves[3].classification, Classification::Best);
+        assert!(report.accuracy(Colour::White).unwrap() < report.accuracy(Colour::Black).unwrap());
+        assert_eq!(report.count(Colour::White, Classification::Blunder), 1);
+    }
+
+    #[test]
+    fn empty_game() {
+        let report = analyse(&Game::new(), 1).unwrap();
+        assert!(report.moves.is_empty());
+        assert_eq!(report.accuracy(Colour::White), None);
+    }
+}
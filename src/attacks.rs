@@ -0,0 +1,336 @@
+//! Which squares the pieces attack, and the checks, pins and x-rays that follow.
+//!
+//! A piece attacks the squares it could capture on, whether or not there is
+//! anything to capture there. Unlike its moves, a pawn attacks only diagonally
+//! and a piece also attacks (defends) squares holding pieces of its own colour.
+
+use crate::{Colour, Game, Piece, PieceType};
+
+pub(crate) const KNIGHT_JUMPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+pub(crate) const KING_STEPS: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+/// The square `steps` times (dy, dx) away from `position`, if it is on the board.
+pub(crate) fn offset(board: &[Vec<Option<Piece>>], position: &[usize], (dy, dx): (i32, i32), steps: i32) -> Option<Vec<usize>> {
+    let y = position[0] as i32 + dy * steps;
+    let x = position[1] as i32 + dx * steps;
+    if y < 0 || x < 0 || y >= board.len() as i32 || x >= board[0].len() as i32 {
+        return None;
+    }
+    Some(vec![y as usize, x as usize])
+}
+
+/// Direction a pawn of `colour` moves in, white moves towards y = 0.
+pub(crate) fn pawn_direction(colour: Colour) -> i32 {
+    match colour {
+        Colour::White => -1,
+        Colour::Black => 1,
+    }
+}
+
+/// Whether the piece slides any number of squares in direction (dy, dx).
+pub(crate) fn slides_in(piece_type: PieceType, (dy, dx): (i32, i32)) -> bool {
+    match piece_type {
+        PieceType::ROOK => dy == 0 || dx == 0,
+        PieceType::BISHOP => dy != 0 && dx != 0,
+        PieceType::QUEEN => true,
+        _ => false,
+    }
+}
+
+/// Squares attacked by the piece standing on `position`.
+pub(crate) fn attacked_squares(board: &[Vec<Option<Piece>>], position: &[usize]) -> Vec<Vec<usize>> {
+    let Some(piece) = board[position[0]][position[1]] else { return vec![] };
+    let mut squares = vec![];
+    match piece.piece_type {
+        PieceType::PAWN => {
+            let dy = pawn_direction(piece.color);
+            squares.extend([-1, 1].iter().filter_map(|dx| offset(board, position, (dy, *dx), 1)));
+        },
+        PieceType::KNIGHT => squares.extend(KNIGHT_JUMPS.iter().filter_map(|jump| offset(board, position, *jump, 1))),
+        PieceType::KING => squares.extend(KING_STEPS.iter().filter_map(|step| offset(board, position, *step, 1))),
+        _ => {
+            for direction in KING_STEPS.iter().filter(|d| slides_in(piece.piece_type, **d)) {
+                let mut steps = 1;
+                while let Some(square) = offset(board, position, *direction, steps) {
+                    let blocked = board[square[0]][square[1]].is_some();
+                    squares.push(square);
+                    if blocked {
+                        break;
+                    }
+                    steps += 1;
+                }
+            }
+        },
+    }
+    squares
+}
+
+/// Positions of the pieces attacking `square`, of either colour.
+pub(crate) fn attackers(board: &[Vec<Option<Piece>>], square: &[usize]) -> Vec<Vec<usize>> {
+    let mut found = vec![];
+    let piece_at = |position: &Vec<usize>| board[position[0]][position[1]];
+
+    for step in KING_STEPS {
+        // Sliding pieces, the first piece in each direction
+        let mut steps = 1;
+        while let Some(position) = offset(board, square, step, steps) {
+            if let Some(piece) = piece_at(&position) {
+                let adjacent_attack = steps == 1 && match piece.piece_type {
+                    PieceType::KING => true,
+                    // A pawn attacks diagonally forward, so it stands diagonally behind
+                    PieceType::PAWN => step.1 != 0 && step.0 == -pawn_direction(piece.color),
+                    _ => false,
+                };
+                if adjacent_attack || slides_in(piece.piece_type, step) {
+                    found.push(position);
+                }
+                break;
+            }
+            steps += 1;
+        }
+    }
+    for jump in KNIGHT_JUMPS {
+        if let Some(position) = offset(board, square, jump, 1) {
+            if piece_at(&position).is_some_and(|piece| piece.piece_type == PieceType::KNIGHT) {
+                found.push(position);
+            }
+        }
+    }
+    found
+}
+
+pub(crate) fn is_attacked(board: &[Vec<Option<Piece>>], square: &[usize], by: Colour) -> bool {
+    attackers(board, square).iter().any(|position| board[position[0]][position[1]].is_some_and(|piece| piece.color == by))
+}
+
+pub(crate) fn find_king(board: &[Vec<Option<Piece>>], colour: Colour) -> Option<Vec<usize>> {
+    for (y, row) in board.iter().enumerate() {
+        for (x, square) in row.iter().enumerate() {
+            if *square == Some(Piece { color: colour, piece_type: PieceType::KING }) {
+                return Some(vec![y, x]);
+            }
+        }
+    }
+    None
+}
+
+/// Returns true if moving the piece on `from` to `to` leaves its own king attacked.
+pub(crate) fn leaves_king_in_check(board: &[Vec<Option<Piece>>], from: &[usize], to: &[usize]) -> bool {
+    let Some(piece) = board[from[0]][from[1]] else { return false };
+    let mut temp_board = board.to_vec();
+    temp_board[to[0]][to[1]] = Some(piece);
+    temp_board[from[0]][from[1]] = None;
+    match find_king(&temp_board, piece.color) {
+        Some(king) => is_attacked(&temp_board, &king, piece.color.opposite()),
+        None => false,
+    }
+}
+
+impl Game {
+    /// Returns true if any piece of colour `by` attacks `square`.
+    pub fn is_square_attacked(&self, square: &[usize], by: Colour) -> bool {
+        is_attacked(&self.board, square, by)
+    }
+
+    /// Positions of all pieces, of both colours, that attack `square`.
+    pub fn attackers_of(&self, square: &[usize]) -> Vec<Vec<usize>> {
+        attackers(&self.board, square)
+    }
+
+    /// For every square, the number of `colour`'s pieces attacking it.
+    pub fn attack_map(&self, colour: Colour) -> Vec<Vec<u8>> {
+        let mut map = vec![vec![0; self.board[0].len()]; self.board.len()];
+        for (y, row) in self.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                if square.is_some_and(|piece| piece.color == colour) {
+                    for attacked in attacked_squares(&self.board, &[y, x]) {
+                        map[attacked[0]][attacked[1]] += 1;
+                    }
+                }
+            }
+        }
+        map
+    }
+}
+
+/// A sliding piece attacking through one piece (the blocker) to another (the target).
+#[derive(Clone, Debug, PartialEq)]
+pub struct XRay {
+    pub attacker: Vec<usize>,
+    pub blocker: Vec<usize>,
+    pub target: Vec<usize>,
+    /// Direction (dy, dx) from the attacker towards the target.
+    pub direction: (i32, i32),
+}
+
+/// A piece that can't leave the line between its king and an enemy slider.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pin {
+    pub pinned: Vec<usize>,
+    pub pinner: Vec<usize>,
+    /// Direction (dy, dx) from the king towards the pinner, the pinned piece may only move along it.
+    pub direction: (i32, i32),
+}
+
+impl Game {
+    /// Returns true if `colour`'s king is attacked.
+    pub fn is_in_check(&self, colour: Colour) -> bool {
+        match find_king(&self.board, colour) {
+            Some(king) => self.is_square_attacked(&king, colour.opposite()),
+            None => false,
+        }
+    }
+
+    /// Positions of the pieces giving check to the player to move.
+    pub fn checkers(&self) -> Vec<Vec<usize>> {
+        let Some(king) = find_king(&self.board, self.active_colour) else { return vec![] };
+        self.attackers_of(&king).into_iter()
+            .filter(|position| self.board[position[0]][position[1]].is_some_and(|piece| piece.color != self.active_colour))
+            .collect()
+    }
+
+    /// Every line where one of `colour`'s sliding pieces reaches a piece through exactly one other piece.
+    pub fn x_rays(&self, colour: Colour) -> Vec<XRay> {
+        let mut found = vec![];
+        for (y, row) in self.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                let Some(piece) = square else { continue };
+                if piece.color != colour {
+                    continue;
+                }
+                for direction in KING_STEPS.iter().filter(|d| slides_in(piece.piece_type, **d)) {
+                    let mut pieces_on_line = vec![];
+                    let mut steps = 1;
+                    while let Some(position) = offset(&self.board, &[y, x], *direction, steps) {
+                        if self.board[position[0]][position[1]].is_some() {
+                            pieces_on_line.push(position);
+                            if pieces_on_line.len() == 2 {
+                                break;
+                            }
+                        }
+                        steps += 1;
+                    }
+                    if let [blocker, target] = pieces_on_line.as_slice() {
+                        found.push(XRay {
+                            attacker: vec![y, x],
+                            blocker: blocker.clone(),
+                            target: target.clone(),
+                            direction: *direction,
+                        });
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// `colour`'s pieces that are pinned to their king.
+    pub fn pinned_pieces(&self, colour: Colour) -> Vec<Pin> {
+        let piece_at = |position: &Vec<usize>| self.board[position[0]][position[1]];
+        self.x_rays(colour.opposite()).into_iter()
+            .filter(|x_ray| piece_at(&x_ray.blocker).is_some_and(|piece| piece.color == colour))
+            .filter(|x_ray| piece_at(&x_ray.target) == Some(Piece { color: colour, piece_type: PieceType::KING }))
+            .map(|x_ray| Pin {
+                pinned: x_ray.blocker,
+                pinner: x_ray.attacker,
+                direction: (-x_ray.direction.0, -x_ray.direction.1),
+            })
+            .collect()
+    }
+
+    /// Attacks by `colour`'s sliding pieces that one of `colour`'s own pieces
+    /// is blocking, and that would be discovered if the blocker moved away.
+    pub fn discovered_attacks(&self, colour: Colour) -> Vec<XRay> {
+        let piece_at = |position: &Vec<usize>| self.board[position[0]][position[1]];
+        self.x_rays(colour).into_iter()
+            .filter(|x_ray| piece_at(&x_ray.blocker).is_some_and(|piece| piece.color == colour))
+            .filter(|x_ray| piece_at(&x_ray.target).is_some_and(|piece| piece.color != colour))
+            .collect()
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Pin, XRay};
+    use crate::{Colour, Game};
+
+    #[test]
+    fn attacked_squares_at_start() {
+        let game = Game::new();
+        // e3 is attacked by the d and f pawns, f3 by two pawns and the g1 knight
+        assert!(game.is_square_attacked(&[5, 4], Colour::White));
+        assert!(!game.is_square_attacked(&[4, 4], Colour::White));
+        let map = game.attack_map(Colour::White);
+        assert_eq!(map[5][5], 3);
+        assert_eq!(map[5][4], 2);
+        assert_eq!(map[4][4], 0);
+        // The rook on a1 defends a2 and b1
+        assert_eq!(map[7][0], 0);
+        assert_eq!(map[6][0], 1);
+    }
+
+    #[test]
+    fn attackers_of_square() {
+        let game = Game::from_fen("4k3/8/8/3p4/4P3/2N5/8/R3K2B w - - 0 1").unwrap();
+        let mut attackers = game.attackers_of(&[3, 3]);
+        attackers.sort();
+        // d5 is attacked by the e4 pawn and the c3 knight, the bishop on h1 is blocked
+        assert_eq!(attackers, vec![vec![4, 4], vec![5, 2]]);
+        let mut attackers = game.attackers_of(&[4, 4]);
+        attackers.sort();
+        assert_eq!(attackers, vec![vec![3, 3], vec![5, 2], vec![7, 7]]);
+        assert!(game.is_square_attacked(&[4, 4], Colour::Black));
+    }
+
+    #[test]
+    fn pinned_piece_cannot_move() {
+        let game = Game::from_fen("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.get_possible_moves(&game.board, &vec![6,4], false), Some(vec![]));
+        let game = Game::from_fen("4k3/4r3/8/8/8/8/8/3K4 w - - 0 1").unwrap();
+        let mut moves = game.get_possible_moves(&game.board, &vec![7,3], false).unwrap();
+        moves.sort();
+        assert_eq!(moves, vec![vec![6, 2], vec![6, 3], vec![7, 2]]);
+    }
+
+    #[test]
+    fn checkers() {
+        let game = Game::from_fen("4k3/8/8/1B6/8/8/4R3/4K3 b - - 0 1").unwrap();
+        assert!(game.is_in_check(Colour::Black));
+        let mut checkers = game.checkers();
+        checkers.sort();
+        assert_eq!(checkers, vec![vec![3, 1], vec![6, 4]]);
+        assert!(Game::new().checkers().is_empty());
+    }
+
+    #[test]
+    fn pins() {
+        // The knight on e7 is pinned by the rook, the d7 pawn by the bishop
+        let game = Game::from_fen("4k3/3pn3/8/1B6/8/8/4R3/4K3 b - - 0 1").unwrap();
+        let mut pins = game.pinned_pieces(Colour::Black);
+        pins.sort_by_key(|pin| pin.pinned.clone());
+        assert_eq!(pins, vec![
+            Pin { pinned: vec![1, 3], pinner: vec![3, 1], direction: (1, -1) },
+            Pin { pinned: vec![1, 4], pinner: vec![6, 4], direction: (1, 0) },
+        ]);
+        assert!(game.pinned_pieces(Colour::White).is_empty());
+    }
+
+    #[test]
+    fn discovered_attacks() {
+        // Moving the white knight discovers an attack from the rook on the queen
+        let game = Game::from_fen("3qk3/8/8/3N4/8/8/8/3RK3 w - - 0 1").unwrap();
+        assert_eq!(game.discovered_attacks(Colour::White), vec![XRay {
+            attacker: vec![7, 3],
+            blocker: vec![3, 3],
+            target: vec![0, 3],
+            direction: (-1, 0),
+        }]);
+        // Seen from black it is an x-ray through an enemy piece, not a discovered attack
+        assert!(game.discovered_attacks(Colour::Black).is_empty());
+        assert_eq!(game.x_rays(Colour::Black).len(), 1);
+    }
+}
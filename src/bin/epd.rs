@@ -0,0 +1,26 @@
+//! Runs the engine on an EPD test suite, see `emriks_chess::epd`.
+//!
+//! Usage: epd <file> [depth], the depth defaults to 3.
+
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use emriks_chess::epd::{read_epd, run_suite};
+
+fn main() -> ExitCode {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("Usage: epd <file> [depth]");
+        return ExitCode::FAILURE;
+    };
+    let depth = env::args().nth(2).and_then(|depth| depth.parse().ok()).unwrap_or(3);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("Can't read {}: {}", path, error);
+            return ExitCode::FAILURE;
+        },
+    };
+    println!("{}", run_suite(&read_epd(&text), depth));
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,13 @@
+//! Hosts games over TCP, see `emriks_chess::server` for the protocol.
+//!
+//! Usage: server [address], the address defaults to 127.0.0.1:7878.
+
+use std::env;
+use std::net::TcpListener;
+
+fn main() -> std::io::Result<()> {
+    let address = env::args().nth(1).unwrap_or_else(|| "127.0.0.1:7878".to_string());
+    let listener = TcpListener::bind(&address)?;
+    println!("Listening on {}", listener.local_addr()?);
+    emriks_chess::server::serve(listener)
+}
@@ -0,0 +1,7 @@
+//! Talks UCI on standard input and output, see `emriks_chess::uci`.
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    emriks_chess::uci::run(io::stdin().lock(), io::stdout())
+}
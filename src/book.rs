@@ -0,0 +1,300 @@
+//! Opening books in the Polyglot `.bin` format.
+//!
+//! A book is a list of 16 byte big-endian entries sorted by key: the Zobrist
+//! hash of the position (8 bytes), the move (2 bytes), a weight (2 bytes) and
+//! a learn value (4 bytes) that isn't used here.
+//!
+//! The keys have to be made with the same Zobrist keys as the book, see
+//! `zobrist` for how to read books made by other programs.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::pgn::read_games;
+use crate::zobrist::Zobrist;
+use crate::{Game, GameResult, Move, PieceType};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub encoded_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+/// A book move that is legal in the position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookMove {
+    pub book_move: Move,
+    pub weight: u16,
+}
+
+/// Polyglot move encoding: the to square in bits 0-5 and the from square in
+/// bits 6-11, both counted from a1, and the promotion piece in bits 12-14
+/// (1 knight, 2 bishop, 3 rook, 4 queen).
+pub fn encode_move(m: &Move) -> u16 {
+    let square = |position: &[usize]| ((7 - position[0]) * 8 + position[1]) as u16;
+    let promotion = match m.promotion {
+        Some(PieceType::KNIGHT) => 1,
+        Some(PieceType::BISHOP) => 2,
+        Some(PieceType::ROOK) => 3,
+        Some(PieceType::QUEEN) => 4,
+        _ => 0,
+    };
+    promotion << 12 | square(&m.from) << 6 | square(&m.to)
+}
+
+pub fn decode_move(encoded: u16) -> Move {
+    let position = |square: u16| vec![7 - (square as usize / 8 % 8), square as usize % 8];
+    Move {
+        from: position(encoded >> 6 & 0x3f),
+        to: position(encoded & 0x3f),
+        promotion: match encoded >> 12 & 7 {
+            1 => Some(PieceType::KNIGHT),
+            2 => Some(PieceType::BISHOP),
+            3 => Some(PieceType::ROOK),
+            4 => Some(PieceType::QUEEN),
+            _ => None,
+        },
+    }
+}
+
+pub struct Book {
+    entries: Vec<BookEntry>,
+    zobrist: Zobrist,
+}
+
+impl Book {
+    /// Book with the given entries, which are sorted by key.
+    pub fn new(mut entries: Vec<BookEntry>, zobrist: Zobrist) -> Book {
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+        Book { entries, zobrist }
+    }
+
+    /// Reads a book, using `zobrist` for looking up positions.
+    pub fn read<R: Read>(mut reader: R, zobrist: Zobrist) -> io::Result<Book> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() % 16 != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "book size isn't a multiple of 16 bytes"));
+        }
+        let entries = bytes.chunks_exact(16).map(|chunk| BookEntry {
+            key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+            encoded_move: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap()),
+        }).collect();
+        Ok(Book::new(entries, zobrist))
+    }
+
+    /// Opens a book file made with the default Zobrist keys.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Book> {
+        Book::read(File::open(path)?, Zobrist::new())
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for entry in &self.entries {
+            writer.write_all(&entry.key.to_be_bytes())?;
+            writer.write_all(&entry.encoded_move.to_be_bytes())?;
+            writer.write_all(&entry.weight.to_be_bytes())?;
+            writer.write_all(&entry.learn.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[BookEntry] {
+        &self.entries
+    }
+
+    /// Legal book moves in the position, highest weight first.
+    pub fn moves(&self, game: &Game) -> Vec<BookMove> {
+        let key = self.zobrist.hash(game);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let legal_moves = game.legal_moves();
+        self.entries[start..].iter()
+            .take_while(|entry| entry.key == key)
+            .map(|entry| BookMove { book_move: decode_move(entry.encoded_move), weight: entry.weight })
+            .filter(|m| legal_moves.contains(&m.book_move))
+            .collect()
+    }
+
+    /// The book move with the highest weight.
+    pub fn best_move(&self, game: &Game) -> Option<Move> {
+        self.moves(game).into_iter().next().map(|m| m.book_move)
+    }
+
+    /// Picks a move with probability proportional to its weight, `roll` being any random number.
+    pub fn weighted_move(&self, game: &Game, roll: u64) -> Option<Move> {
+        let moves = self.moves(game);
+        let total: u64 = moves.iter().map(|m| m.weight as u64).sum();
+        if total == 0 {
+            return moves.into_iter().next().map(|m| m.book_move);
+        }
+        let mut roll = roll % total;
+        for m in moves {
+            if roll < m.weight as u64 {
+                return Some(m.book_move);
+            }
+            roll -= m.weight as u64;
+        }
+        None
+    }
+
+    /// Picks a random move weighted by the book.
+    pub fn random_move(&self, game: &Game) -> Option<Move> {
+        self.weighted_move(game, RandomState::new().build_hasher().finish())
+    }
+}
+
+/// Builds a book from played games. Every time a move is played it gets 2
+/// points if the player won, 1 for a draw or unknown result and 0 for a loss.
+pub struct BookBuilder {
+    zobrist: Zobrist,
+    max_ply: usize,
+    weights: HashMap<(u64, u16), u64>,
+}
+
+impl BookBuilder {
+    pub fn new() -> BookBuilder {
+        BookBuilder {
+            zobrist: Zobrist::new(),
+            max_ply: 30,
+            weights: HashMap::new(),
+        }
+    }
+
+    pub fn zobrist(mut self, zobrist: Zobrist) -> BookBuilder {
+        self.zobrist = zobrist;
+        self
+    }
+
+    /// Only the first `max_ply` half moves of each game are added.
+    pub fn max_ply(mut self, max_ply: usize) -> BookBuilder {
+        self.max_ply = max_ply;
+        self
+    }
+
+    /// Adds the moves of a game, replayed from its start position.
+    pub fn add_game(&mut self, game: &Game, result: Option<GameResult>) {
+        let Ok(mut position) = Game::from_fen(&game.start_fen) else { return };
+        for m in game.history.iter().take(self.max_ply) {
+            let points = match result {
+                Some(GameResult::Win(colour)) if colour == position.active_colour => 2,
+                Some(GameResult::Win(_)) => 0,
+                _ => 1,
+            };
+            *self.weights.entry((self.zobrist.hash(&position), encode_move(m))).or_insert(0) += points;
+            if position.play_move(m).is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Adds every game in a PGN collection and returns how many were read.
+    pub fn add_pgn(&mut self, pgn: &str) -> usize {
+        let games = read_games(pgn);
+        for game in &games {
+            self.add_game(&game.game, game.result);
+        }
+        games.len()
+    }
+
+    pub fn build(self) -> Book {
+        // Weights are scaled down if they don't fit in 16 bits
+        let max = self.weights.values().copied().max().unwrap_or(0);
+        let scale = max.div_ceil(u16::MAX as u64).max(1);
+        let entries = self.weights.into_iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|((key, encoded_move), weight)| BookEntry {
+                key,
+                encoded_move,
+                weight: (weight / scale).max(1) as u16,
+                learn: 0,
+            })
+            .collect();
+        Book::new(entries, self.zobrist)
+    }
+}
+
+impl Default for BookBuilder {
+    fn default() -> BookBuilder {
+        BookBuilder::new()
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_move, encode_move, Book, BookBuilder};
+    use crate::zobrist::Zobrist;
+    use crate::{Game, Move};
+
+    const GAMES: &str = r#"
+[Result "1-0"]
+1. e4 e5 2. Nf3 1-0
+
+[Result "1-0"]
+1. e4 c5 1-0
+
+[Result "1/2-1/2"]
+1. d4 d5 1/2-1/2
+"#;
+
+    #[test]
+    fn move_encoding() {
+        // e2e4 is from square 12 to square 28
+        let m = Move::parse("e2e4").unwrap();
+        assert_eq!(encode_move(&m), 12 << 6 | 28);
+        assert_eq!(decode_move(encode_move(&m)), m);
+        let m = Move::parse("b7b8n").unwrap();
+        assert_eq!(encode_move(&m), 1 << 12 | 49 << 6 | 57);
+        assert_eq!(decode_move(encode_move(&m)), m);
+    }
+
+    #[test]
+    fn build_and_probe() {
+        let mut builder = BookBuilder::new();
+        assert_eq!(builder.add_pgn(GAMES), 3);
+        let book = builder.build();
+
+        let game = Game::new();
+        let moves = book.moves(&game);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].book_move, Move::parse("e2e4").unwrap());
+        assert_eq!(moves[0].weight, 4);
+        assert_eq!(moves[1].weight, 1);
+        assert_eq!(book.best_move(&game), Move::parse("e2e4"));
+        assert_eq!(book.weighted_move(&game, 3), Move::parse("e2e4"));
+        assert_eq!(book.weighted_move(&game, 4), Move::parse("d2d4"));
+
+        // Black lost both games after 1. e4, so those moves get no weight and are left out
+        let mut game = Game::new();
+        game.make_move(vec![6,4], vec![4,4]);
+        assert!(book.moves(&game).is_empty());
+
+        let mut game = Game::new();
+        game.make_move(vec![6,3], vec![4,3]);
+        assert_eq!(book.random_move(&game), Move::parse("d7d5"));
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut builder = BookBuilder::new().max_ply(1);
+        builder.add_pgn(GAMES);
+        let book = builder.build();
+        let mut bytes = vec![];
+        book.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 2 * 16);
+
+        let read = Book::read(bytes.as_slice(), Zobrist::new()).unwrap();
+        assert_eq!(read.entries(), book.entries());
+        assert!(Book::read(&bytes[1..], Zobrist::new()).is_err());
+    }
+}
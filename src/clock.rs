@@ -0,0 +1,351 @@
+//! Chess clock for timed games.
+//!
+//! The clock doesn't measure time itself, the caller tells it how long each
+//! move took. That keeps it usable both for real time play and for replaying
+//! games where the times are already known.
+
+use std::time::Duration;
+
+use crate::{Colour, Game, GameResult, GameState, Move, ResultReason};
+
+/// Time added (or not taken) for every move.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Bonus {
+    None,
+    /// Fischer increment: the time is added after every move.
+    Increment(Duration),
+    /// Bronstein delay: the time used is given back, up to the delay.
+    Bronstein(Duration),
+    /// Simple (US) delay: the clock doesn't start running until the delay has passed.
+    SimpleDelay(Duration),
+}
+
+/// One period of a time control. `moves` is the number of moves that has to be
+/// made within the period, `None` means the rest of the game.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stage {
+    pub moves: Option<u32>,
+    pub time: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeControl {
+    stages: Vec<Stage>,
+    bonus: Bonus,
+}
+
+impl TimeControl {
+    /// Multi-stage time control, e.g. 40/90+30 is 90 minutes for 40 moves,
+    /// then 30 minutes for the rest of the game with a 30 second increment:
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use emriks_chess::clock::{Bonus, Stage, TimeControl};
+    ///
+    /// let control = TimeControl::new(vec![
+    ///     Stage { moves: Some(40), time: Duration::from_secs(90 * 60) },
+    ///     Stage { moves: None, time: Duration::from_secs(30 * 60) },
+    /// ], Bonus::Increment(Duration::from_secs(30))).unwrap();
+    /// ```
+    ///
+    /// If the last stage has a move count it is repeated. `None` if there are no stages.
+    pub fn new(stages: Vec<Stage>, bonus: Bonus) -> Option<TimeControl> {
+        if stages.is_empty() {
+            return None;
+        }
+        Some(TimeControl { stages, bonus })
+    }
+
+    fn single_stage(time: Duration, bonus: Bonus) -> TimeControl {
+        TimeControl { stages: vec![Stage { moves: None, time }], bonus }
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    pub fn bonus(&self) -> Bonus {
+        self.bonus
+    }
+
+    /// The whole game has to be played within `time`.
+    pub fn sudden_death(time: Duration) -> TimeControl {
+        TimeControl::single_stage(time, Bonus::None)
+    }
+
+    pub fn fischer(time: Duration, increment: Duration) -> TimeControl {
+        TimeControl::single_stage(time, Bonus::Increment(increment))
+    }
+
+    pub fn bronstein(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::single_stage(time, Bonus::Bronstein(delay))
+    }
+
+    pub fn simple_delay(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::single_stage(time, Bonus::SimpleDelay(delay))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    stage: [usize; 2],
+    stage_moves: [u32; 2],
+    moves_made: [u32; 2],
+    flagged: Option<Colour>,
+}
+
+fn index(colour: Colour) -> usize {
+    match colour {
+        Colour::White => 0,
+        Colour::Black => 1,
+    }
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        // A deserialized control may have no stages
+        let time = control.stages.first().map_or(Duration::ZERO, |stage| stage.time);
+        Clock {
+            control,
+            remaining: [time; 2],
+            stage: [0; 2],
+            stage_moves: [0; 2],
+            moves_made: [0; 2],
+            flagged: None,
+        }
+    }
+
+    pub fn time_control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /// Time left on `colour`'s clock.
+    pub fn remaining(&self, colour: Colour) -> Duration {
+        self.remaining[index(colour)]
+    }
+
+    /// Number of moves `colour` has completed on this clock.
+    pub fn moves_made(&self, colour: Colour) -> u32 {
+        self.moves_made[index(colour)]
+    }
+
+    /// The player whose flag has fallen, if any.
+    pub fn flagged(&self) -> Option<Colour> {
+        self.flagged
+    }
+
+    /// The time actually taken from the clock when thinking for `elapsed`.
+    fn debit(&self, elapsed: Duration) -> Duration {
+        match self.control.bonus {
+            Bonus::SimpleDelay(delay) => elapsed.saturating_sub(delay),
+            _ => elapsed,
+        }
+    }
+
+    /// Returns true if `colour` runs out of time by thinking for `elapsed` on the current move.
+    pub fn would_flag(&self, colour: Colour, elapsed: Duration) -> bool {
+        self.flagged == Some(colour) || self.debit(elapsed) >= self.remaining(colour)
+    }
+
+    /// Marks `colour` as having run out of time.
+    pub fn flag(&mut self, colour: Colour) {
+        self.remaining[index(colour)] = Duration::ZERO;
+        self.flagged = Some(colour);
+    }
+
+    /// Stops `colour`'s clock after a move that took `spent`.
+    /// Returns false if the flag fell before the move was completed.
+    pub fn punch(&mut self, colour: Colour, spent: Duration) -> bool {
+        if self.would_flag(colour, spent) {
+            self.flag(colour);
+            return false;
+        }
+
+        let i = index(colour);
+        self.remaining[i] -= self.debit(spent);
+        // Saturating, so a very long time control can't overflow
+        match self.control.bonus {
+            Bonus::Increment(increment) => self.remaining[i] = self.remaining[i].saturating_add(increment),
+            Bonus::Bronstein(delay) => self.remaining[i] = self.remaining[i].saturating_add(spent.min(delay)),
+            _ => {},
+        }
+
+        self.moves_made[i] += 1;
+        self.stage_moves[i] += 1;
+
+        // Move on to the next stage once the move count is reached
+        let stages = &self.control.stages;
+        if stages.get(self.stage[i]).is_some_and(|stage| Some(self.stage_moves[i]) == stage.moves) {
+            self.stage[i] = (self.stage[i] + 1).min(stages.len() - 1);
+            self.stage_moves[i] = 0;
+            self.remaining[i] = self.remaining[i].saturating_add(stages[self.stage[i]].time);
+        }
+
+        true
+    }
+}
+
+impl Game {
+    /// Starts a clock with the given time control for both players.
+    pub fn set_time_control(&mut self, control: TimeControl) {
+        self.clock = Some(Clock::new(control));
+    }
+
+    /// Like `make_move`, but also charges the player to move for `spent`.
+    /// If the flag falls before the move the game is over and the move isn't made.
+    pub fn make_timed_move(&mut self, from: Vec<usize>, to: Vec<usize>, spent: Duration) -> Option<GameState> {
+        if self.check_flag(spent) == GameState::GameOver {
+            return Some(GameState::GameOver);
+        }
+
+        let colour = self.active_colour;
+        let state = self.make_move(from, to)?;
+        if let Some(clock) = &mut self.clock {
+            clock.punch(colour, spent);
+        }
+        Some(state)
+    }
+
+    /// `make_timed_move` promoting to the piece in `m`, like `play_move`.
+    pub fn play_timed_move(&mut self, m: &Move, spent: Duration) -> Option<GameState> {
+        self.with_promotion(m, |game| game.make_timed_move(m.from.clone(), m.to.clone(), spent))
+    }
+
+    /// Checks whether the player to move has run out of time after thinking for `elapsed`.
+    /// On flag fall the game is lost on time, or drawn if the opponent can't checkmate.
+    pub fn check_flag(&mut self, elapsed: Duration) -> GameState {
+        if self.state == GameState::GameOver {
+            return self.state;
+        }
+
+        let colour = self.active_colour;
+        let flagged = match &mut self.clock {
+            Some(clock) if clock.would_flag(colour, elapsed) => {
+                clock.flag(colour);
+                true
+            },
+            _ => false,
+        };
+
+        if flagged {
+            if self.has_insufficient_material(colour.opposite()) {
+                self.end_game(Some(GameResult::Draw), ResultReason::TimeoutVsInsufficientMaterial);
+            }
+            else {
+                self.end_game(Some(GameResult::Win(colour.opposite())), ResultReason::Timeout);
+            }
+        }
+
+        self.state
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Bonus, Clock, Stage, TimeControl};
+    use crate::{Colour, Game, GameResult, GameState, Piece, PieceType, ResultReason};
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn sudden_death_flag() {
+        let mut clock = Clock::new(TimeControl::sudden_death(secs(60)));
+        assert!(clock.punch(Colour::White, secs(40)));
+        assert_eq!(clock.remaining(Colour::White), secs(20));
+        assert_eq!(clock.remaining(Colour::Black), secs(60));
+        assert!(!clock.punch(Colour::White, secs(20)));
+        assert_eq!(clock.flagged(), Some(Colour::White));
+    }
+
+    #[test]
+    fn fischer_increment() {
+        let mut clock = Clock::new(TimeControl::fischer(secs(60), secs(5)));
+        clock.punch(Colour::White, secs(2));
+        assert_eq!(clock.remaining(Colour::White), secs(63));
+    }
+
+    #[test]
+    fn bronstein_and_simple_delay() {
+        let mut bronstein = Clock::new(TimeControl::bronstein(secs(60), secs(5)));
+        bronstein.punch(Colour::White, secs(3));
+        bronstein.punch(Colour::Black, secs(8));
+        assert_eq!(bronstein.remaining(Colour::White), secs(60));
+        assert_eq!(bronstein.remaining(Colour::Black), secs(57));
+
+        // With a simple delay the player may think for longer than the time left
+        let mut simple = Clock::new(TimeControl::simple_delay(secs(10), secs(5)));
+        assert!(!simple.would_flag(Colour::White, secs(12)));
+        assert!(bronstein.would_flag(Colour::White, secs(60)));
+        simple.punch(Colour::White, secs(12));
+        assert_eq!(simple.remaining(Colour::White), secs(3));
+    }
+
+    #[test]
+    fn multi_stage_adds_time() {
+        let control = TimeControl::new(vec![
+            Stage { moves: Some(40), time: secs(90 * 60) },
+            Stage { moves: None, time: secs(30 * 60) },
+        ], Bonus::Increment(secs(30))).unwrap();
+        let mut clock = Clock::new(control);
+        for _ in 0..39 {
+            clock.punch(Colour::White, secs(30));
+        }
+        assert_eq!(clock.remaining(Colour::White), secs(90 * 60));
+        clock.punch(Colour::White, secs(30));
+        assert_eq!(clock.remaining(Colour::White), secs(120 * 60));
+        assert_eq!(clock.moves_made(Colour::White), 40);
+    }
+
+    #[test]
+    fn no_stages_and_long_controls() {
+        assert_eq!(TimeControl::new(vec![], Bonus::None), None);
+
+        let mut clock = Clock::new(TimeControl::fischer(Duration::MAX - secs(1), secs(5)));
+        assert!(clock.punch(Colour::White, secs(2)));
+        assert_eq!(clock.remaining(Colour::White), Duration::MAX);
+
+        let control = TimeControl::new(vec![Stage { moves: Some(1), time: Duration::MAX }], Bonus::Bronstein(secs(5))).unwrap();
+        let mut clock = Clock::new(control);
+        assert!(clock.punch(Colour::Black, secs(3)));
+        assert_eq!(clock.remaining(Colour::Black), Duration::MAX);
+    }
+
+    #[test]
+    fn lose_on_time() {
+        let mut game = Game::new();
+        game.set_time_control(TimeControl::sudden_death(secs(60)));
+        assert_eq!(game.make_timed_move(vec![6,4], vec![4,4], secs(10)), Some(GameState::InProgress));
+        assert_eq!(game.make_timed_move(vec![1,4], vec![3,4], secs(61)), Some(GameState::GameOver));
+        assert_eq!(game.result, Some(GameResult::Win(Colour::White)));
+        assert_eq!(game.board[1][4], Some(Piece { color: Colour::Black, piece_type: PieceType::PAWN }));
+        assert_eq!(game.make_move(vec![6,3], vec![4,3]), Some(GameState::GameOver));
+    }
+
+    #[test]
+    fn draw_on_time_with_insufficient_material() {
+        let mut game = Game::new();
+        game.board = vec![vec![None; 8]; 8];
+        game.board[7][4] = Some(Piece { color: Colour::White, piece_type: PieceType::KING });
+        game.board[7][5] = Some(Piece { color: Colour::White, piece_type: PieceType::ROOK });
+        game.board[0][4] = Some(Piece { color: Colour::Black, piece_type: PieceType::KING });
+        game.board[0][1] = Some(Piece { color: Colour::Black, piece_type: PieceType::KNIGHT });
+        game.set_time_control(TimeControl::sudden_death(secs(60)));
+        assert_eq!(game.check_flag(secs(60)), GameState::GameOver);
+        assert_eq!(game.result, Some(GameResult::Draw));
+        assert_eq!(game.result_reason, Some(ResultReason::TimeoutVsInsufficientMaterial));
+    }
+}
@@ -0,0 +1,176 @@
+//! Setting up positions by hand, and checking that they can be played.
+//!
+//! An edited position becomes the start of the game: the history is cleared
+//! and `start_fen` is updated.
+
+use std::fmt;
+
+use crate::{Colour, Game, Piece, PieceType};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PositionProblem {
+    /// The board isn't 8 by 8.
+    BoardSize,
+    /// A player doesn't have exactly one king.
+    KingCount { colour: Colour, count: usize },
+    PawnOnBackRank { square: Vec<usize> },
+    TooManyPawns { colour: Colour, count: usize },
+    TooManyPieces { colour: Colour, count: usize },
+    /// The player who just moved is in check.
+    OpponentInCheck { colour: Colour },
+}
+
+impl fmt::Display for PositionProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionProblem::BoardSize => write!(f, "the board isn't 8x8"),
+            PositionProblem::KingCount { colour, count } => write!(f, "{:?} has {} kings", colour, count),
+            PositionProblem::PawnOnBackRank { square } => write!(f, "pawn on {}", crate::notation::square_name(square)),
+            PositionProblem::TooManyPawns { colour, count } => write!(f, "{:?} has {} pawns", colour, count),
+            PositionProblem::TooManyPieces { colour, count } => write!(f, "{:?} has {} pieces", colour, count),
+            PositionProblem::OpponentInCheck { colour } => write!(f, "{:?} is in check but it isn't their move", colour),
+        }
+    }
+}
+
+/// Everything wrong with a position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionError {
+    pub problems: Vec<PositionProblem>,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let problems: Vec<String> = self.problems.iter().map(|problem| problem.to_string()).collect();
+        write!(f, "{}", problems.join(", "))
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+fn on_board(square: &[usize]) -> bool {
+    square.len() == 2 && square[0] < 8 && square[1] < 8
+}
+
+impl Game {
+    /// The edited position is where the game starts from now on.
+    fn position_edited(&mut self) {
+        self.history.clear();
+        self.undo_stack.clear();
+        self.start_fen = self.to_fen();
+    }
+
+    /// Puts `piece` on `square`, replacing what was there. Returns false if the square isn't on the board.
+    pub fn set_piece(&mut self, square: &[usize], piece: Piece) -> bool {
+        if !on_board(square) {
+            return false;
+        }
+        self.board[square[0]][square[1]] = Some(piece);
+        self.position_edited();
+        true
+    }
+
+    /// Empties `square`. Returns false if the square isn't on the board.
+    pub fn clear_square(&mut self, square: &[usize]) -> bool {
+        if !on_board(square) {
+            return false;
+        }
+        self.board[square[0]][square[1]] = None;
+        self.position_edited();
+        true
+    }
+
+    pub fn set_side_to_move(&mut self, colour: Colour) {
+        self.active_colour = colour;
+        self.position_edited();
+    }
+
+    /// Checks that the position could come up in a game.
+    pub fn validate(&self) -> Result<(), PositionError> {
+        if self.board.len() != 8 || self.board.iter().any(|row| row.len() != 8) {
+            return Err(PositionError { problems: vec![PositionProblem::BoardSize] });
+        }
+
+        let mut problems = vec![];
+        for colour in [Colour::White, Colour::Black] {
+            let pieces: Vec<Piece> = self.board.iter().flatten().flatten().copied().filter(|piece| piece.color == colour).collect();
+            let count_of = |piece_type| pieces.iter().filter(|piece| piece.piece_type == piece_type).count();
+            if count_of(PieceType::KING) != 1 {
+                problems.push(PositionProblem::KingCount { colour, count: count_of(PieceType::KING) });
+            }
+            if count_of(PieceType::PAWN) > 8 {
+                problems.push(PositionProblem::TooManyPawns { colour, count: count_of(PieceType::PAWN) });
+            }
+            if pieces.len() > 16 {
+                problems.push(PositionProblem::TooManyPieces { colour, count: pieces.len() });
+            }
+        }
+
+        for y in [0, 7] {
+            for x in 0..8 {
+                if self.board[y][x].is_some_and(|piece| piece.piece_type == PieceType::PAWN) {
+                    problems.push(PositionProblem::PawnOnBackRank { square: vec![y, x] });
+                }
+            }
+        }
+
+        let opponent = self.active_colour.opposite();
+        if self.is_in_check(opponent) {
+            problems.push(PositionProblem::OpponentInCheck { colour: opponent });
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(PositionError { problems })
+        }
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::PositionProblem;
+    use crate::{Colour, Game, Piece, PieceType};
+
+    #[test]
+    fn edit_position() {
+        let mut game = Game::new();
+        game.make_move(vec![6, 4], vec![4, 4]);
+        assert!(game.clear_square(&[6, 3]));
+        assert!(game.set_piece(&[4, 3], Piece { color: Colour::White, piece_type: PieceType::KNIGHT }));
+        assert!(!game.set_piece(&[8, 0], Piece { color: Colour::White, piece_type: PieceType::KNIGHT }));
+        game.set_side_to_move(Colour::White);
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/3NP3/8/PPP2PPP/RNBQKBNR w - - 0 1");
+        assert_eq!(game.start_fen, game.to_fen());
+        assert!(game.history.is_empty());
+        assert_eq!(game.validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let mut game = Game::from_fen("P3k3/8/8/8/8/8/4r3/4K2K b - - 0 1").unwrap();
+        let problems = game.validate().unwrap_err().problems;
+        assert_eq!(problems, vec![
+            PositionProblem::KingCount { colour: Colour::White, count: 2 },
+            PositionProblem::PawnOnBackRank { square: vec![0, 0] },
+            PositionProblem::OpponentInCheck { colour: Colour::White },
+        ]);
+
+        game.clear_square(&[7, 7]);
+        game.clear_square(&[0, 0]);
+        game.set_side_to_move(Colour::White);
+        assert_eq!(game.validate(), Ok(()));
+    }
+
+    #[test]
+    fn wrong_board_size() {
+        let mut game = Game::new();
+        game.board.pop();
+        assert_eq!(game.validate().unwrap_err().problems, vec![PositionProblem::BoardSize]);
+    }
+}
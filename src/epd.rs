@@ -0,0 +1,231 @@
+//! EPD test suites such as WAC and STS, and running the engine on them.
+//!
+//! An EPD line is the first four fields of a FEN followed by operations like
+//! `bm Qxf7+; id "WAC.001";`. Castling and en passant fields are read but
+//! ignored, like in `Game::from_fen`.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::notation::FenError;
+use crate::search::search;
+use crate::{Game, Move};
+
+#[derive(Debug, PartialEq)]
+pub enum EpdError {
+    MissingField,
+    Fen(FenError),
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::MissingField => write!(f, "missing field"),
+            EpdError::Fen(error) => write!(f, "invalid position: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+pub struct EpdRecord {
+    pub game: Game,
+    /// Opcodes and their operands in the order they were given, with quotes removed.
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+/// Splits on `separator`, except inside quotes.
+fn split_unquoted(text: &str, separator: impl Fn(char) -> bool) -> Vec<String> {
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        if c == '"' {
+            quoted = !quoted;
+            part.push(c);
+        }
+        else if separator(c) && !quoted {
+            parts.push(std::mem::take(&mut part));
+        }
+        else {
+            part.push(c);
+        }
+    }
+    parts.push(part);
+    parts.into_iter().map(|part| part.trim().to_string()).filter(|part| !part.is_empty()).collect()
+}
+
+impl EpdRecord {
+    /// Reads one EPD line.
+    pub fn parse(line: &str) -> Result<EpdRecord, EpdError> {
+        let line = line.trim();
+        let mut fields = vec![];
+        let mut rest = line;
+        for _ in 0..4 {
+            let (field, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() {
+                return Err(EpdError::MissingField);
+            }
+            fields.push(field);
+            rest = after.trim_start();
+        }
+
+        let operations: Vec<(String, Vec<String>)> = split_unquoted(rest, |c| c == ';').iter()
+            .map(|operation| {
+                let mut words = split_unquoted(operation, char::is_whitespace).into_iter();
+                let opcode = words.next().unwrap_or_default();
+                (opcode, words.map(|word| word.trim_matches('"').to_string()).collect())
+            })
+            .collect();
+
+        // The move counters may be given as operations
+        let operand = |name: &str| operations.iter().find(|(opcode, _)| opcode == name).and_then(|(_, operands)| operands.first());
+        let halfmove = operand("hmvc").map_or("0", |value| value.as_str());
+        let fullmove = operand("fmvn").map_or("1", |value| value.as_str());
+        let fen = format!("{} {} {}", fields.join(" "), halfmove, fullmove);
+        let game = Game::from_fen(&fen).map_err(EpdError::Fen)?;
+        Ok(EpdRecord { game, operations })
+    }
+
+    /// Operands of the first operation with the given opcode.
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id").and_then(|operands| operands.first()).map(|id| id.as_str())
+    }
+
+    /// Comment `c0` to `c9`.
+    pub fn comment(&self, number: u8) -> Option<&str> {
+        self.operation(&format!("c{}", number)).and_then(|operands| operands.first()).map(|comment| comment.as_str())
+    }
+
+    fn moves(&self, opcode: &str) -> Vec<Move> {
+        self.operation(opcode).unwrap_or_default().iter().filter_map(|san| self.game.parse_san(san)).collect()
+    }
+
+    /// The `bm` moves that can be played in `game`.
+    pub fn best_moves(&self) -> Vec<Move> {
+        self.moves("bm")
+    }
+
+    /// The `am` moves that can be played in `game`.
+    pub fn avoid_moves(&self) -> Vec<Move> {
+        self.moves("am")
+    }
+
+    /// Whether `m` is one of the best moves and none of the moves to avoid.
+    /// A `bm` whose moves can't be played here, such as castling, is never solved.
+    pub fn is_solved_by(&self, m: &Move) -> bool {
+        let solves_best = self.operation("bm").is_none() || self.best_moves().contains(m);
+        solves_best && !self.avoid_moves().contains(m)
+    }
+}
+
+/// Reads every EPD line of a suite, skipping empty lines, `#` comments and lines that can't be read.
+pub fn read_epd(text: &str) -> Vec<EpdRecord> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .filter_map(|line| EpdRecord::parse(line).ok())
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionResult {
+    pub id: Option<String>,
+    pub found: Option<Move>,
+    pub solved: bool,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuiteReport {
+    pub positions: Vec<PositionResult>,
+    pub elapsed: Duration,
+}
+
+impl SuiteReport {
+    pub fn solved(&self) -> usize {
+        self.positions.iter().filter(|position| position.solved).count()
+    }
+}
+
+impl fmt::Display for SuiteReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, position) in self.positions.iter().enumerate() {
+            let found = position.found.as_ref().map_or("-".to_string(), |m| m.to_string());
+            writeln!(f, "{} {} {} {}ms", position.id.as_deref().unwrap_or(&(i + 1).to_string()), found,
+                if position.solved { "solved" } else { "failed" }, position.elapsed.as_millis())?;
+        }
+        write!(f, "solved {}/{} in {}ms", self.solved(), self.positions.len(), self.elapsed.as_millis())
+    }
+}
+
+/// Searches every position `depth` half moves deep and checks the move found.
+pub fn run_suite(records: &[EpdRecord], depth: u32) -> SuiteReport {
+    let start = Instant::now();
+    let positions = records.iter()
+        .map(|record| {
+            let position_start = Instant::now();
+            let found = search(&record.game, depth).best_move;
+            PositionResult {
+                id: record.id().map(str::to_string),
+                solved: found.as_ref().is_some_and(|m| record.is_solved_by(m)),
+                found,
+                elapsed: position_start.elapsed(),
+            }
+        })
+        .collect();
+    SuiteReport { positions, elapsed: start.elapsed() }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{read_epd, run_suite, EpdError, EpdRecord};
+    use crate::Move;
+
+    #[test]
+    fn parse_operations() {
+        let record = EpdRecord::parse(r#"4k3/8/8/3q4/8/8/3R4/4K3 w - - bm Rxd5; am Ke2; id "test 1"; c0 "a; quoted comment"; hmvc 3;"#).unwrap();
+        assert_eq!(record.id(), Some("test 1"));
+        assert_eq!(record.comment(0), Some("a; quoted comment"));
+        assert_eq!(record.best_moves(), vec![Move::parse("d2d5").unwrap()]);
+        assert_eq!(record.avoid_moves(), vec![Move::parse("e1e2").unwrap()]);
+        assert_eq!(record.game.halfmove_clock, 3);
+        assert!(record.is_solved_by(&Move::parse("d2d5").unwrap()));
+
+        assert_eq!(EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w -").err(), Some(EpdError::MissingField));
+    }
+
+    #[test]
+    fn unplayable_best_move() {
+        // `Game` can't castle, so no move solves this
+        let record = EpdRecord::parse("4k3/8/8/8/8/8/8/4K2R w K - bm O-O;").unwrap();
+        assert!(record.best_moves().is_empty());
+        assert!(!record.is_solved_by(&Move::parse("e1e2").unwrap()));
+
+        let record = EpdRecord::parse("4k3/8/8/8/8/8/8/4K2R w K - am O-O;").unwrap();
+        assert!(record.is_solved_by(&Move::parse("e1e2").unwrap()));
+    }
+
+    #[test]
+    fn run_small_suite() {
+        let suite = "
+# The engine plays Ra8 in the last one, which is marked as a move to avoid
+4k3/8/8/3q4/8/8/3R4/4K3 w - - bm Rxd5; id \"queen\";
+6k1/5ppp/8/8/8/8/8/R3K3 w - - bm Ra8#; id \"mate\";
+6k1/5ppp/8/8/8/8/8/R3K3 w - - am Ra8; id \"avoid\";
+";
+        let records = read_epd(suite);
+        assert_eq!(records.len(), 3);
+        let report = run_suite(&records, 1);
+        assert_eq!(report.solved(), 2);
+        assert!(!report.positions[2].solved);
+        assert!(report.to_string().ends_with(&format!("solved 2/3 in {}ms", report.elapsed.as_millis())));
+    }
+}
@@ -0,0 +1,146 @@
+//! Callbacks for changes to a game, so a GUI doesn't have to compare boards.
+//!
+//! Observers belong to one `Game`: a clone starts without any, so searching
+//! or analysing a copy of the game doesn't send events. `Game` has no
+//! castling, so there is no event for it.
+
+use crate::{Colour, Game, GameResult, Move, Piece, ResultReason};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    MoveMade { from: Vec<usize>, to: Vec<usize>, piece: Piece },
+    /// `piece` was taken on `square`.
+    Capture { square: Vec<usize>, piece: Piece },
+    /// The pawn on `square` became `piece`.
+    Promotion { square: Vec<usize>, piece: Piece },
+    /// `colour`'s king on `king` is attacked.
+    Check { colour: Colour, king: Vec<usize> },
+    GameOver { result: Option<GameResult>, reason: ResultReason },
+    /// `piece` went back from `to` to `from` and `captured`, if any, was put back on `to`.
+    Undo { from: Vec<usize>, to: Vec<usize>, piece: Piece, captured: Option<Piece> },
+}
+
+type Observer = Box<dyn FnMut(&GameEvent) + Send>;
+
+#[derive(Default)]
+pub(crate) struct Observers {
+    next_id: usize,
+    list: Vec<(usize, Observer)>,
+}
+
+impl Clone for Observers {
+    fn clone(&self) -> Observers {
+        Observers::default()
+    }
+}
+
+impl Game {
+    /// Calls `observer` with every event from now on. Returns an id for `unsubscribe`.
+    pub fn subscribe(&mut self, observer: impl FnMut(&GameEvent) + Send + 'static) -> usize {
+        let id = self.observers.next_id;
+        self.observers.next_id += 1;
+        self.observers.list.push((id, Box::new(observer)));
+        id
+    }
+
+    /// Removes an observer. Returns false if there was none with that id.
+    pub fn unsubscribe(&mut self, id: usize) -> bool {
+        let count = self.observers.list.len();
+        self.observers.list.retain(|(observer_id, _)| *observer_id != id);
+        self.observers.list.len() != count
+    }
+
+    pub(crate) fn emit(&mut self, event: GameEvent) {
+        for (_, observer) in self.observers.list.iter_mut() {
+            observer(&event);
+        }
+    }
+
+    /// Sends the events for a move that has just been made.
+    pub(crate) fn emit_move(&mut self, m: &Move, piece: Piece, captured: Option<Piece>) {
+        if self.observers.list.is_empty() {
+            return;
+        }
+        self.emit(GameEvent::MoveMade { from: m.from.clone(), to: m.to.clone(), piece });
+        if let Some(captured) = captured {
+            self.emit(GameEvent::Capture { square: m.to.clone(), piece: captured });
+        }
+        if let Some(promoted) = self.board[m.to[0]][m.to[1]].filter(|promoted| *promoted != piece) {
+            self.emit(GameEvent::Promotion { square: m.to.clone(), piece: promoted });
+        }
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::GameEvent;
+    use crate::{Colour, Game, GameResult, Piece, PieceType, ResultReason};
+
+    /// Subscribes to `game` and returns the list the events end up in.
+    fn record(game: &mut Game) -> Arc<Mutex<Vec<GameEvent>>> {
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        game.subscribe(move |event| sink.lock().unwrap().push(event.clone()));
+        events
+    }
+
+    #[test]
+    fn capture_and_check() {
+        let mut game = Game::from_fen("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let events = record(&mut game);
+        game.make_move(vec![7, 3], vec![3, 3]);
+        game.make_move(vec![0, 4], vec![0, 5]);
+        game.make_move(vec![3, 3], vec![3, 5]);
+        let rook = Piece { color: Colour::White, piece_type: PieceType::ROOK };
+        let events = events.lock().unwrap();
+        assert_eq!(events[0], GameEvent::MoveMade { from: vec![7, 3], to: vec![3, 3], piece: rook });
+        assert_eq!(events[1], GameEvent::Capture { square: vec![3, 3], piece: Piece { color: Colour::Black, piece_type: PieceType::PAWN } });
+        assert_eq!(events.last(), Some(&GameEvent::Check { colour: Colour::Black, king: vec![0, 5] }));
+    }
+
+    #[test]
+    fn promotion_game_over_and_undo() {
+        let mut game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let events = record(&mut game);
+        game.set_promotion(PieceType::KNIGHT);
+        game.make_move(vec![1, 1], vec![0, 1]);
+        assert!(events.lock().unwrap().contains(&GameEvent::Promotion {
+            square: vec![0, 1],
+            piece: Piece { color: Colour::White, piece_type: PieceType::KNIGHT },
+        }));
+
+        game.undo();
+        assert_eq!(events.lock().unwrap().last(), Some(&GameEvent::Undo {
+            from: vec![1, 1],
+            to: vec![0, 1],
+            piece: Piece { color: Colour::White, piece_type: PieceType::PAWN },
+            captured: None,
+        }));
+
+        game.resign(Colour::White);
+        assert_eq!(events.lock().unwrap().last(), Some(&GameEvent::GameOver {
+            result: Some(GameResult::Win(Colour::Black)),
+            reason: ResultReason::Resignation,
+        }));
+    }
+
+    #[test]
+    fn clones_and_unsubscribed_observers_get_nothing() {
+        let mut game = Game::new();
+        let events = record(&mut game);
+        let mut copy = game.clone();
+        copy.make_move(vec![6, 4], vec![4, 4]);
+        assert!(events.lock().unwrap().is_empty());
+
+        assert!(game.unsubscribe(0));
+        assert!(!game.unsubscribe(0));
+        game.make_move(vec![6, 4], vec![4, 4]);
+        assert!(events.lock().unwrap().is_empty());
+    }
+}
@@ -3,7 +3,39 @@
 
 use std::fmt;
 
+pub mod analysis;
+pub mod attacks;
+pub mod book;
+pub mod clock;
+pub mod editor;
+pub mod epd;
+pub mod events;
+pub mod motifs;
+pub mod nnue;
+pub mod notation;
+pub mod opponent;
+pub mod outcome;
+pub mod pgn;
+pub mod problems;
+pub mod puzzle;
+pub mod raster;
+pub mod search;
+pub mod see;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod server;
+pub mod svg;
+pub mod tablebase;
+pub mod tt;
+pub mod uci;
+pub mod variant;
+pub mod zobrist;
+
+use clock::Clock;
+use events::{GameEvent, Observers};
+
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GameState {
     InProgress,
     Check,
@@ -11,18 +43,75 @@ pub enum GameState {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Colour {
     White,
     Black,
 }
 
+impl Colour {
+    /// The colour of the other player.
+    pub fn opposite(&self) -> Colour {
+        match self {
+            Colour::White => Colour::Black,
+            Colour::Black => Colour::White,
+        }
+    }
+}
+
+/// How a finished game ended.
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameResult {
+    Win(Colour),
+    Draw,
+}
+
+/// Formats the result the way it's written in PGN, e.g. `1-0`.
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::Win(Colour::White) => write!(f, "1-0"),
+            GameResult::Win(Colour::Black) => write!(f, "0-1"),
+            GameResult::Draw => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
+/// Why a finished game ended.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResultReason {
+    KingCaptured,
+    Resignation,
+    DrawAgreement,
+    Timeout,
+    /// The flag fell but the opponent couldn't have checkmated.
+    TimeoutVsInsufficientMaterial,
+}
+
+impl fmt::Display for ResultReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            ResultReason::KingCaptured => "king captured",
+            ResultReason::Resignation => "resignation",
+            ResultReason::DrawAgreement => "draw by agreement",
+            ResultReason::Timeout => "time forfeit",
+            ResultReason::TimeoutVsInsufficientMaterial => "timeout vs insufficient material",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
     pub color: Colour,
     pub piece_type: PieceType,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceType {
     PAWN,
     ROOK,
@@ -32,17 +121,67 @@ pub enum PieceType {
     KING
 }
 
+/// A move from one position to another, both stored as [y, x].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Move {
+    pub from: Vec<usize>,
+    pub to: Vec<usize>,
+    /// The piece a pawn reaching the last rank becomes, `None` for other moves.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    /// A move that isn't a promotion.
+    pub fn new(from: Vec<usize>, to: Vec<usize>) -> Move {
+        Move { from, to, promotion: None }
+    }
+}
+
+/// The pieces a pawn can be promoted to.
+pub const PROMOTION_TYPES: [PieceType; 4] = [PieceType::QUEEN, PieceType::ROOK, PieceType::BISHOP, PieceType::KNIGHT];
+
 
 /* IMPORTANT:
  * - Document well!
  * - Write well structured and clean code!
  */
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Game {
     pub board: Vec<Vec<Option<Piece>>>,
     pub active_colour: Colour,
     pub state: GameState,
     pub promotion_type: PieceType,
+    pub result: Option<GameResult>,
+    pub result_reason: Option<ResultReason>,
+    /// The player with a pending draw offer.
+    pub draw_offer: Option<Colour>,
+    pub clock: Option<Clock>,
+    /// Moves made since the start position, in order.
+    pub history: Vec<Move>,
+    /// FEN of the position the game started from.
+    pub start_fen: String,
+    /// Half moves since the last capture or pawn move.
+    pub halfmove_clock: u32,
+    /// Starts at 1 and is increased after every black move.
+    pub fullmove_number: u32,
+    /// What `undo` needs for each move in `history`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    undo_stack: Vec<Undo>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observers: Observers,
+}
+
+/// Everything about the position before a move that can't be worked out from the move.
+#[derive(Clone)]
+struct Undo {
+    piece: Piece,
+    captured: Option<Piece>,
+    halfmove_clock: u32,
+    draw_offer: Option<Colour>,
 }
 
 impl Game {
@@ -54,6 +193,16 @@ impl Game {
             active_colour: Colour::White,
             state: GameState::InProgress,
             promotion_type: PieceType::QUEEN,
+            result: None,
+            result_reason: None,
+            draw_offer: None,
+            clock: None,
+            history: vec![],
+            start_fen: notation::STARTING_FEN.to_string(),
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            undo_stack: vec![],
+            observers: Observers::default(),
         };
         //Setting the correct pieces:
         //Pawns
@@ -85,6 +234,29 @@ impl Game {
         return game
     }
 
+    /// Returns true if a pawn moving from `from` to `to` is promoted.
+    pub fn is_promotion(&self, from: &[usize], to: &[usize]) -> bool {
+        (to[0] == 0 || to[0] == 7) && self.board[from[0]][from[1]].is_some_and(|piece| piece.piece_type == PieceType::PAWN)
+    }
+
+    /// Like `make_move`, promoting to the piece in `m` instead of `promotion_type`.
+    /// A promotion piece on a move that isn't a promotion makes the move illegal.
+    pub fn play_move(&mut self, m: &Move) -> Option<GameState> {
+        self.with_promotion(m, |game| game.make_move(m.from.clone(), m.to.clone()))
+    }
+
+    /// Runs `f` with `promotion_type` set to the promotion of `m`.
+    pub(crate) fn with_promotion<T>(&mut self, m: &Move, f: impl FnOnce(&mut Game) -> Option<T>) -> Option<T> {
+        let Some(promotion) = m.promotion else { return f(self) };
+        if !PROMOTION_TYPES.contains(&promotion) || !self.is_promotion(&m.from, &m.to) {
+            return None;
+        }
+        let promotion_type = std::mem::replace(&mut self.promotion_type, promotion);
+        let result = f(self);
+        self.promotion_type = promotion_type;
+        result
+    }
+
     /// If the current game state is InProgress and the move is legal,
     /// move a piece and return the resulting state of the game.
     /// I have changed from it having String parameters to Vec parameters
@@ -121,18 +293,54 @@ impl Game {
             None => return None,
         }
 
+        let captured = self.board[to[0]][to[1]];
+        self.undo_stack.push(Undo {
+            piece,
+            captured,
+            halfmove_clock: self.halfmove_clock,
+            draw_offer: self.draw_offer,
+        });
+
+        // A draw offer from the opponent expires when the move is made
+        if self.draw_offer == Some(piece.color.opposite()) {
+            self.draw_offer = None;
+        }
+
+        // Record the move
+        if piece.piece_type == PieceType::PAWN || self.board[to[0]][to[1]].is_some() {
+            self.halfmove_clock = 0;
+        }
+        else {
+            self.halfmove_clock += 1;
+        }
+        if piece.color == Colour::Black {
+            self.fullmove_number += 1;
+        }
+        let promotion = self.is_promotion(&from, &to).then_some(self.promotion_type);
+        let m = Move { from: from.clone(), to: to.clone(), promotion };
+        // A pawn on the last rank is promoted
+        let placed = match promotion {
+            Some(promotion) => Piece { color: piece.color, piece_type: promotion },
+            None => piece,
+        };
+
         // Check if it wins the game
         if let Some(board_piece) = self.board[to[0]][to[1]] {
             if board_piece.piece_type == PieceType::KING {
-                self.board[to[0]][to[1]] = Some(piece);
+                self.board[to[0]][to[1]] = Some(placed);
                 self.board[from[0]][from[1]] = None;
+                self.emit_move(&m, piece, captured);
+                self.history.push(m);
+                self.end_game(Some(GameResult::Win(piece.color)), ResultReason::KingCaptured);
                 return Some(GameState::GameOver);
             }
         }
 
         // Update board
-        self.board[to[0]][to[1]] = Some(piece);
+        self.board[to[0]][to[1]] = Some(placed);
         self.board[from[0]][from[1]] = None;
+        self.emit_move(&m, piece, captured);
+        self.history.push(m);
 
 
         // Change active_color
@@ -145,39 +353,12 @@ impl Game {
         
 
         // Check if it is Check
-        //find king
-        let mut king = vec![10,10]; //Value out of range on purpose
-        for y in 0..8 {
-            for x in 0..8 {
-                if let Some(board_piece) = self.board[y][x] {
-                    if board_piece.piece_type == PieceType::KING && board_piece.color != piece.color {
-                        king = vec![y, x];
-                    }
-                }
+        if let Some(king) = attacks::find_king(&self.board, self.active_colour) {
+            if self.is_square_attacked(&king, piece.color) {
+                self.emit(GameEvent::Check { colour: self.active_colour, king });
+                return Some(GameState::Check);
             }
         }
-        for y in 0..8 {
-            for x in 0..8 {
-                if let Some(board_piece) = self.board[y][x] {
-                    if board_piece.color == piece.color {
-                        //It is one of your pieces
-                        let moves_option = self.get_possible_moves(&self.board, &vec![y, x], true);
-                        match moves_option {
-                            Some(moves) => {
-                                if moves.contains(&king) {
-                                    //Check detected!
-                                    return Some(GameState::Check);
-                                }
-                            },
-                            None => continue,
-                        }   
-                    }
-                }
-            }
-        }
-
-
-
 
         return Some(GameState::InProgress);
     }
@@ -195,11 +376,135 @@ impl Game {
         self.state
     }
 
+    /// Ends the game with the given result and records why.
+    pub(crate) fn end_game(&mut self, result: Option<GameResult>, reason: ResultReason) {
+        self.state = GameState::GameOver;
+        self.result = result;
+        self.result_reason = Some(reason);
+        self.draw_offer = None;
+        self.emit(GameEvent::GameOver { result, reason });
+    }
+
+    /// Takes back the last move and returns it. A finished game is reopened.
+    /// The clock isn't changed. Games read with serde can't undo the moves made before.
+    pub fn undo(&mut self) -> Option<Move> {
+        if self.undo_stack.len() != self.history.len() {
+            return None;
+        }
+        let undo = self.undo_stack.pop()?;
+        let m = self.history.pop()?;
+        self.board[m.from[0]][m.from[1]] = Some(undo.piece);
+        self.board[m.to[0]][m.to[1]] = undo.captured;
+        self.active_colour = undo.piece.color;
+        if undo.piece.color == Colour::Black {
+            self.fullmove_number -= 1;
+        }
+        self.halfmove_clock = undo.halfmove_clock;
+        self.draw_offer = undo.draw_offer;
+        self.state = GameState::InProgress;
+        self.result = None;
+        self.result_reason = None;
+        self.emit(GameEvent::Undo { from: m.from.clone(), to: m.to.clone(), piece: undo.piece, captured: undo.captured });
+        Some(m)
+    }
+
+    /// `colour` resigns and the opponent wins.
+    pub fn resign(&mut self, colour: Colour) -> GameState {
+        if self.state != GameState::GameOver {
+            self.end_game(Some(GameResult::Win(colour.opposite())), ResultReason::Resignation);
+        }
+        self.state
+    }
+
+    /// `colour` offers a draw. The offer stands until the opponent accepts,
+    /// declines or makes a move. Returns false if the offer can't be made.
+    pub fn offer_draw(&mut self, colour: Colour) -> bool {
+        if self.state == GameState::GameOver || self.draw_offer == Some(colour.opposite()) {
+            return false;
+        }
+        self.draw_offer = Some(colour);
+        true
+    }
+
+    /// `colour` accepts the opponent's draw offer, which ends the game.
+    /// Returns false if there was no offer to accept.
+    pub fn accept_draw(&mut self, colour: Colour) -> bool {
+        if self.state == GameState::GameOver || self.draw_offer != Some(colour.opposite()) {
+            return false;
+        }
+        self.end_game(Some(GameResult::Draw), ResultReason::DrawAgreement);
+        true
+    }
+
+    /// `colour` declines the opponent's draw offer.
+    /// Returns false if there was no offer to decline.
+    pub fn decline_draw(&mut self, colour: Colour) -> bool {
+        if self.draw_offer != Some(colour.opposite()) {
+            return false;
+        }
+        self.draw_offer = None;
+        true
+    }
+
+    /// Returns true if `colour` doesn't have enough material left to ever checkmate,
+    /// that is a lone king or a king with a single bishop or knight.
+    pub fn has_insufficient_material(&self, colour: Colour) -> bool {
+        let mut minor_pieces = 0;
+        for piece in self.board.iter().flatten().flatten() {
+            if piece.color != colour {
+                continue;
+            }
+            match piece.piece_type {
+                PieceType::KING => {},
+                PieceType::BISHOP | PieceType::KNIGHT => minor_pieces += 1,
+                _ => return false,
+            }
+        }
+        minor_pieces <= 1
+    }
+
+    /// All legal moves of the player to move. A promotion is one move per piece in `PROMOTION_TYPES`.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = vec![];
+        for (y, row) in self.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                match square {
+                    Some(piece) if piece.color == self.active_colour => {
+                        let from = vec![y, x];
+                        for to in self.get_possible_moves(&self.board, &from, false).unwrap_or_default() {
+                            if self.is_promotion(&from, &to) {
+                                for promotion in PROMOTION_TYPES {
+                                    moves.push(Move { from: from.clone(), to: to.clone(), promotion: Some(promotion) });
+                                }
+                            }
+                            else {
+                                moves.push(Move::new(from.clone(), to));
+                            }
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+        moves
+    }
+
+    /// The player to move is in check and has no legal moves.
+    pub fn is_checkmate(&self) -> bool {
+        self.is_in_check(self.active_colour) && self.legal_moves().is_empty()
+    }
+
+    /// The player to move isn't in check but has no legal moves.
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_in_check(self.active_colour) && self.legal_moves().is_empty()
+    }
+
     /// If a piece is standing on the given tile, return all possible
     /// new positions of that piece. Don't forget to the rules for check.
     ///
     /// (optional) Don't forget to include en passent and castling.
     /// I have changed from params being String to &Vec<usize> and return value to Option<Vec<Vec<usize>>>
+    /// If call_is_recursive is true, moves that leave the own king in check are kept.
     pub fn get_possible_moves(&self, board: &Vec<Vec<Option<Piece>>>, position: &Vec<usize>, call_is_recursive: bool) -> Option<Vec<Vec<usize>>> {
         let piece = board[position[0]][position[1]].unwrap();
         
@@ -283,46 +588,9 @@ impl Game {
             movement_positions.retain(|pos| !forbidden_positions.contains(pos));
 
 
-            //Check if move leads to a check on your king, it shouldn't be allowed in that case
+            //Moves that leave your own king attacked aren't allowed
             if !call_is_recursive {
-                //find king
-                let mut king = vec![10,10]; //Value out of range on purpose
-                for y in 0..8 {
-                    for x in 0..8 {
-                        if let Some(board_piece) = board[y][x] {
-                            if board_piece.piece_type == PieceType::KING && board_piece.color == piece.color {
-                                king = vec![y, x];
-                            }
-                        }
-                    }
-                }
-                for action in movement_positions.clone() {
-                    let mut temp_board = board.clone();
-                    temp_board[action[0]][action[1]] = Some(piece);
-                    temp_board[position[0]][position[1]] = None;
-                    for y in 0..8 {
-                        for x in 0..8 {
-                            if let Some(board_piece) = temp_board[y][x] {
-                                if board_piece.color != piece.color {
-                                    //It is an enemy piece
-                                    let moves_option = self.get_possible_moves(&temp_board, &vec![y, x], true);
-                                    match moves_option {
-                                        Some(moves) => {
-                                            if moves.contains(&king) {
-                                                //Check detected!
-                                                //Remove that move from movement_positions since it would lead to a check 
-                                                if let Some(check_move_index) = movement_positions.iter().position(|pos| pos == &action){
-                                                    movement_positions.remove(check_move_index);
-                                                }
-                                            }
-                                        },
-                                        None => continue,
-                                    }   
-                                }
-                            }
-                        }
-                    }
-                }
+                movement_positions.retain(|action| !attacks::leaves_king_in_check(board, position, action));
             }
 
 
@@ -406,46 +674,9 @@ impl Game {
             //remove forbidden
             movement_positions.retain(|pos| !forbidden_positions.contains(pos));
 
-            //Check if move leads to a check on your king, it shouldn't be allowed in that case
+            //Moves that leave your own king attacked aren't allowed
             if !call_is_recursive {
-                //find king
-                let mut king = vec![10,10]; //Value out of range on purpose
-                for y in 0..8 {
-                    for x in 0..8 {
-                        if let Some(board_piece) = board[y][x] {
-                            if board_piece.piece_type == PieceType::KING && board_piece.color == piece.color {
-                                king = vec![y, x];
-                            }
-                        }
-                    }
-                }
-                for action in movement_positions.clone() {
-                    let mut temp_board = board.clone();
-                    temp_board[action[0]][action[1]] = Some(piece);
-                    temp_board[position[0]][position[1]] = None;
-                    for y in 0..8 {
-                        for x in 0..8 {
-                            if let Some(board_piece) = temp_board[y][x] {
-                                if board_piece.color != piece.color {
-                                    //It is an enemy piece
-                                    let moves_option = self.get_possible_moves(&temp_board, &vec![y, x], true);
-                                    match moves_option {
-                                        Some(moves) => {
-                                            if moves.contains(&king) {
-                                                //Check detected!
-                                                //Remove that move from movement_positions since it would lead to a check 
-                                                if let Some(check_move_index) = movement_positions.iter().position(|pos| pos == &action){
-                                                    movement_positions.remove(check_move_index);
-                                                }
-                                            }
-                                        },
-                                        None => continue,
-                                    }   
-                                }
-                            }
-                        }
-                    }
-                }
+                movement_positions.retain(|action| !attacks::leaves_king_in_check(board, position, action));
             }
 
             return Some(movement_positions);
@@ -545,46 +776,9 @@ impl Game {
             //remove forbidden
             movement_positions.retain(|pos| !forbidden_positions.contains(pos));
 
-            //Check if move leads to a check on your king, it shouldn't be allowed in that case
+            //Moves that leave your own king attacked aren't allowed
             if !call_is_recursive {
-                //find king
-                let mut king = vec![10,10]; //Value out of range on purpose
-                for y in 0..8 {
-                    for x in 0..8 {
-                        if let Some(board_piece) = board[y][x] {
-                            if board_piece.piece_type == PieceType::KING && board_piece.color == piece.color {
-                                king = vec![y, x];
-                            }
-                        }
-                    }
-                }
-                for action in movement_positions.clone() {
-                    let mut temp_board = board.clone();
-                    temp_board[action[0]][action[1]] = Some(piece);
-                    temp_board[position[0]][position[1]] = None;
-                    for y in 0..8 {
-                        for x in 0..8 {
-                            if let Some(board_piece) = temp_board[y][x] {
-                                if board_piece.color != piece.color {
-                                    //It is an enemy piece
-                                    let moves_option = self.get_possible_moves(&temp_board, &vec![y, x], true);
-                                    match moves_option {
-                                        Some(moves) => {
-                                            if moves.contains(&king) {
-                                                //Check detected!
-                                                //Remove that move from movement_positions since it would lead to a check 
-                                                if let Some(check_move_index) = movement_positions.iter().position(|pos| pos == &action){
-                                                    movement_positions.remove(check_move_index);
-                                                }
-                                            }
-                                        },
-                                        None => continue,
-                                    }   
-                                }
-                            }
-                        }
-                    }
-                }
+                movement_positions.retain(|action| !attacks::leaves_king_in_check(board, position, action));
             }
             
             return Some(movement_positions);
@@ -728,46 +922,9 @@ impl Game {
             //remove forbidden
             movement_positions.retain(|pos| !forbidden_positions.contains(pos));
 
-            //Check if move leads to a check on your king, it shouldn't be allowed in that case
+            //Moves that leave your own king attacked aren't allowed
             if !call_is_recursive {
-                //find king
-                let mut king = vec![10,10]; //Value out of range on purpose
-                for y in 0..8 {
-                    for x in 0..8 {
-                        if let Some(board_piece) = board[y][x] {
-                            if board_piece.piece_type == PieceType::KING && board_piece.color == piece.color {
-                                king = vec![y, x];
-                            }
-                        }
-                    }
-                }
-                for action in movement_positions.clone() {
-                    let mut temp_board = board.clone();
-                    temp_board[action[0]][action[1]] = Some(piece);
-                    temp_board[position[0]][position[1]] = None;
-                    for y in 0..8 {
-                        for x in 0..8 {
-                            if let Some(board_piece) = temp_board[y][x] {
-                                if board_piece.color != piece.color {
-                                    //It is an enemy piece
-                                    let moves_option = self.get_possible_moves(&temp_board, &vec![y, x], true);
-                                    match moves_option {
-                                        Some(moves) => {
-                                            if moves.contains(&king) {
-                                                //Check detected!
-                                                //Remove that move from movement_positions since it would lead to a check 
-                                                if let Some(check_move_index) = movement_positions.iter().position(|pos| pos == &action){
-                                                    movement_positions.remove(check_move_index);
-                                                }
-                                            }
-                                        },
-                                        None => continue,
-                                    }   
-                                }
-                            }
-                        }
-                    }
-                }
+                movement_positions.retain(|action| !attacks::leaves_king_in_check(board, position, action));
             }
 
             return Some(movement_positions);
@@ -815,37 +972,9 @@ impl Game {
             //remove forbidden
             movement_positions.retain(|pos| !forbidden_positions.contains(pos));
 
-            //Check if move leads to a check on your king, it shouldn't be allowed in that case
+            //Moves that leave your own king attacked aren't allowed
             if !call_is_recursive {
-                for action in movement_positions.clone() {
-                    let king = &action;
-                    let mut temp_board = board.clone();
-                    temp_board[action[0]][action[1]] = Some(piece);
-                    temp_board[position[0]][position[1]] = None;
-                    for y in 0..8 {
-                        for x in 0..8 {
-                            if let Some(board_piece) = temp_board[y][x] {
-                                if board_piece.color != piece.color {
-                                    //It is an enemy piece
-                                    let moves_option = self.get_possible_moves(&temp_board, &vec![y, x], true);
-                                    match moves_option {
-                                        Some(moves) => {
-                                            if moves.contains(king) {
-                                                //Check detected!
-                                                //Remove that move from movement_positions since it would lead to a check 
-                                                if let Some(check_move_index) = movement_positions.iter().position(|pos| pos == &action){
-                                                    movement_positions.remove(check_move_index);
-                                                }
-                                                
-                                            }
-                                        },
-                                        None => continue,
-                                    }   
-                                }
-                            }
-                        }
-                    }
-                }
+                movement_positions.retain(|action| !attacks::leaves_king_in_check(board, position, action));
             }
 
             return Some(movement_positions);
@@ -884,46 +1013,9 @@ impl Game {
             //remove forbidden
             movement_positions.retain(|pos| !forbidden_positions.contains(pos));
 
-            //Check if move leads to a check on your king, it shouldn't be allowed in that case
+            //Moves that leave your own king attacked aren't allowed
             if !call_is_recursive {
-                //find king
-                let mut king = vec![10,10]; //Value out of range on purpose
-                for y in 0..8 {
-                    for x in 0..8 {
-                        if let Some(board_piece) = board[y][x] {
-                            if board_piece.piece_type == PieceType::KING && board_piece.color == piece.color {
-                                king = vec![y, x];
-                            }
-                        }
-                    }
-                }
-                for action in movement_positions.clone() {
-                    let mut temp_board = board.clone();
-                    temp_board[action[0]][action[1]] = Some(piece);
-                    temp_board[position[0]][position[1]] = None;
-                    for y in 0..8 {
-                        for x in 0..8 {
-                            if let Some(board_piece) = temp_board[y][x] {
-                                if board_piece.color != piece.color {
-                                    //It is an enemy piece
-                                    let moves_option = self.get_possible_moves(&temp_board, &vec![y, x], true);
-                                    match moves_option {
-                                        Some(moves) => {
-                                            if moves.contains(&king) {
-                                                //Check detected!
-                                                //Remove that move from movement_positions since it would lead to a check 
-                                                if let Some(check_move_index) = movement_positions.iter().position(|pos| pos == &action){
-                                                    movement_positions.remove(check_move_index);
-                                                }
-                                            }
-                                        },
-                                        None => continue,
-                                    }   
-                                }
-                            }
-                        }
-                    }
-                }
+                movement_positions.retain(|action| !attacks::leaves_king_in_check(board, position, action));
             }
 
             return Some(movement_positions);
@@ -1001,8 +1093,12 @@ impl fmt::Debug for Game {
 #[cfg(test)]
 mod tests {
     use crate::Colour;
+    use crate::GameResult;
+    use crate::Move;
     use crate::Piece;
     use crate::PieceType;
+    use crate::ResultReason;
+    use crate::notation;
 
     use super::Game;
     use super::GameState;
@@ -1187,5 +1283,100 @@ mod tests {
         assert_eq!(game.board[4][6], Some(Piece {color: Colour::Black, piece_type: PieceType::BISHOP}));
     }
 
-    
+    #[test]
+    fn resign() {
+        let mut game = Game::new();
+        assert_eq!(game.resign(Colour::White), GameState::GameOver);
+        assert_eq!(game.result, Some(GameResult::Win(Colour::Black)));
+        assert_eq!(game.result_reason, Some(ResultReason::Resignation));
+        assert_eq!(game.make_move(vec![1,4], vec![3,4]), Some(GameState::GameOver));
+    }
+
+    #[test]
+    fn accept_draw_offer() {
+        let mut game = Game::new();
+        assert!(game.offer_draw(Colour::White));
+        assert!(!game.accept_draw(Colour::White));
+        assert!(game.accept_draw(Colour::Black));
+        assert_eq!(game.get_game_state(), GameState::GameOver);
+        assert_eq!(game.result, Some(GameResult::Draw));
+        assert_eq!(game.result_reason, Some(ResultReason::DrawAgreement));
+    }
+
+    #[test]
+    fn draw_offer_expires_after_opponents_move() {
+        let mut game = Game::new();
+        // The offer survives the move of the player who made it
+        game.offer_draw(Colour::White);
+        game.make_move(vec![6,4], vec![4,4]);
+        assert_eq!(game.draw_offer, Some(Colour::White));
+        game.make_move(vec![1,4], vec![3,4]);
+        assert_eq!(game.draw_offer, None);
+        assert!(!game.accept_draw(Colour::Black));
+
+        game.offer_draw(Colour::White);
+        assert!(game.decline_draw(Colour::Black));
+        assert_eq!(game.draw_offer, None);
+        assert_eq!(game.get_game_state(), GameState::InProgress);
+    }
+
+    #[test]
+    fn promotion_capturing_the_king() {
+        let mut game = Game::from_fen("k7/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.play_move(&Move::parse("b7a8n").unwrap()), Some(GameState::GameOver));
+        assert_eq!(game.result_reason, Some(ResultReason::KingCaptured));
+        assert_eq!(game.board[0][0], Some(Piece { color: Colour::White, piece_type: PieceType::KNIGHT }));
+        assert_eq!(game.history.last().unwrap().promotion, Some(PieceType::KNIGHT));
+
+        assert_eq!(game.undo(), Move::parse("b7a8n"));
+        assert_eq!(game.board[1][1], Some(Piece { color: Colour::White, piece_type: PieceType::PAWN }));
+        assert_eq!(game.board[0][0], Some(Piece { color: Colour::Black, piece_type: PieceType::KING }));
+    }
+
+    #[test]
+    fn king_capture_records_result() {
+        let mut game = Game::new();
+        game.board[1][5] = None;
+        game.board[2][5] = Some(Piece { color: Colour::White, piece_type: PieceType::KNIGHT });
+        game.make_move(vec![2,5], vec![0,4]);
+        assert_eq!(game.result, Some(GameResult::Win(Colour::White)));
+        assert_eq!(game.result_reason, Some(ResultReason::KingCaptured));
+        assert_eq!(format!("{}", game.result.unwrap()), "1-0");
+
+        // Taking the king back reopens the game
+        assert_eq!(game.undo(), Some(Move::new(vec![2,5], vec![0,4])));
+        assert_eq!(game.get_game_state(), GameState::InProgress);
+        assert_eq!(game.result, None);
+        assert_eq!(game.board[0][4], Some(Piece { color: Colour::Black, piece_type: PieceType::KING }));
+    }
+
+    #[test]
+    fn undo_restores_position() {
+        let mut game = Game::new();
+        game.make_move(vec![6,4], vec![4,4]);
+        game.make_move(vec![1,3], vec![3,3]);
+        game.make_move(vec![4,4], vec![3,3]);
+        game.undo();
+        game.undo();
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b - - 0 1");
+        game.undo();
+        assert_eq!(game.to_fen(), notation::STARTING_FEN);
+        assert_eq!(game.undo(), None);
+    }
+
+    #[test]
+    fn underpromotion_is_recorded() {
+        let mut game = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.legal_moves().iter().filter(|m| m.from == vec![1, 0]).count(), 4);
+        assert_eq!(game.play_move(&Move::parse("a7a8r").unwrap()), Some(GameState::Check));
+        assert_eq!(game.to_fen(), "R3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(game.history, vec![Move::parse("a7a8r").unwrap()]);
+        assert_eq!(game.promotion_type, PieceType::QUEEN);
+
+        assert_eq!(game.undo(), Move::parse("a7a8r"));
+        assert_eq!(game.board[1][0], Some(Piece { color: Colour::White, piece_type: PieceType::PAWN }));
+        // Only pawns reaching the last rank are promoted
+        assert_eq!(game.play_move(&Move::parse("e1e2q").unwrap()), None);
+        assert_eq!(game.play_move(&Move { promotion: Some(PieceType::KING), ..Move::parse("a7a8").unwrap() }), None);
+    }
 }
\ No newline at end of file
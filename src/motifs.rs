@@ -0,0 +1,191 @@
+//! Finds tactical motifs in a position, for labelling training positions.
+
+use crate::attacks::{attacked_squares, attackers};
+use crate::{Colour, Game, Move, PieceType};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Motif {
+    /// A piece that is attacked and not defended.
+    Hanging { square: Vec<usize> },
+    /// A knight or queen attacking two or more pieces that it can win.
+    Fork { attacker: Vec<usize>, targets: Vec<Vec<usize>> },
+    /// A sliding piece attacking a valuable piece with a less valuable one behind it.
+    Skewer { attacker: Vec<usize>, front: Vec<usize>, behind: Vec<usize> },
+    /// A rook or queen move to the back rank that would mate, if it were the player's move.
+    BackRankThreat { mating_move: Move },
+    MateInOne { mating_move: Move },
+}
+
+/// A motif and the player who can make use of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub colour: Colour,
+    pub motif: Motif,
+}
+
+impl Game {
+    /// Number of `colour`'s pieces attacking `square`.
+    fn attacker_count(&self, square: &[usize], colour: Colour) -> usize {
+        attackers(&self.board, square).iter()
+            .filter(|position| self.board[position[0]][position[1]].is_some_and(|piece| piece.color == colour))
+            .count()
+    }
+
+    /// `colour`'s pieces, other than the king, that are attacked and not defended.
+    pub fn hanging_pieces(&self, colour: Colour) -> Vec<Vec<usize>> {
+        let mut hanging = vec![];
+        for (y, row) in self.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                let Some(piece) = square else { continue };
+                if piece.color == colour && piece.piece_type != PieceType::KING
+                    && self.attacker_count(&[y, x], colour.opposite()) > 0 && self.attacker_count(&[y, x], colour) == 0 {
+                    hanging.push(vec![y, x]);
+                }
+            }
+        }
+        hanging
+    }
+
+    /// Knight and queen forks by `colour`. A forked piece is the king, a piece
+    /// worth more than the attacker or a piece that isn't defended.
+    pub fn forks(&self, colour: Colour) -> Vec<Motif> {
+        let mut forks = vec![];
+        for (y, row) in self.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                let Some(attacker) = square else { continue };
+                if attacker.color != colour || !matches!(attacker.piece_type, PieceType::KNIGHT | PieceType::QUEEN) {
+                    continue;
+                }
+                let targets: Vec<Vec<usize>> = attacked_squares(&self.board, &[y, x]).into_iter()
+                    .filter(|target| match self.board[target[0]][target[1]] {
+                        Some(piece) if piece.color != colour => {
+                            piece.piece_type == PieceType::KING
+                                || piece.piece_type.value() > attacker.piece_type.value()
+                                || self.attacker_count(target, colour.opposite()) == 0
+                        },
+                        _ => false,
+                    })
+                    .collect();
+                if targets.len() >= 2 {
+                    forks.push(Motif::Fork { attacker: vec![y, x], targets });
+                }
+            }
+        }
+        forks
+    }
+
+    /// Skewers by `colour`'s sliding pieces.
+    pub fn skewers(&self, colour: Colour) -> Vec<Motif> {
+        let value_at = |position: &Vec<usize>| self.board[position[0]][position[1]].map(|piece| piece.piece_type.value());
+        let piece_at = |position: &Vec<usize>| self.board[position[0]][position[1]];
+        self.x_rays(colour).into_iter()
+            .filter(|x_ray| piece_at(&x_ray.blocker).is_some_and(|piece| piece.color != colour))
+            .filter(|x_ray| piece_at(&x_ray.target).is_some_and(|piece| piece.color != colour))
+            .filter(|x_ray| value_at(&x_ray.blocker) > value_at(&x_ray.target))
+            .map(|x_ray| Motif::Skewer { attacker: x_ray.attacker, front: x_ray.blocker, behind: x_ray.target })
+            .collect()
+    }
+
+    /// `colour`'s moves that give mate, whether or not it is `colour`'s move.
+    fn mating_moves(&self, colour: Colour) -> Vec<Move> {
+        let mut game = self.clone();
+        game.active_colour = colour;
+        game.legal_moves().into_iter()
+            .filter(|m| {
+                let mut after = game.clone();
+                after.play_move(m);
+                after.is_checkmate()
+            })
+            .collect()
+    }
+
+    /// Moves that mate for the player to move.
+    pub fn mates_in_one(&self) -> Vec<Move> {
+        self.mating_moves(self.active_colour)
+    }
+
+    /// Rook and queen moves by `colour` to the opponent's back rank that would mate.
+    pub fn back_rank_threats(&self, colour: Colour) -> Vec<Move> {
+        let back_rank = match colour {
+            Colour::White => 0,
+            Colour::Black => 7,
+        };
+        self.mating_moves(colour).into_iter()
+            .filter(|m| m.to[0] == back_rank)
+            .filter(|m| self.board[m.from[0]][m.from[1]].is_some_and(|piece| matches!(piece.piece_type, PieceType::ROOK | PieceType::QUEEN)))
+            .collect()
+    }
+
+    /// Every motif in the position, for both players.
+    pub fn find_motifs(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+        for colour in [Colour::White, Colour::Black] {
+            let mut motifs = vec![];
+            motifs.extend(self.hanging_pieces(colour.opposite()).into_iter().map(|square| Motif::Hanging { square }));
+            motifs.extend(self.forks(colour));
+            motifs.extend(self.skewers(colour));
+            if colour == self.active_colour {
+                motifs.extend(self.mates_in_one().into_iter().map(|mating_move| Motif::MateInOne { mating_move }));
+            }
+            else {
+                motifs.extend(self.back_rank_threats(colour).into_iter().map(|mating_move| Motif::BackRankThreat { mating_move }));
+            }
+            findings.extend(motifs.into_iter().map(|motif| Finding { colour, motif }));
+        }
+        findings
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Finding, Motif};
+    use crate::{Colour, Game, Move};
+
+    #[test]
+    fn hanging_pieces() {
+        // The knight on c6 is attacked by the bishop and not defended, the e5 pawn is defended
+        let game = Game::from_fen("4k3/8/2n5/1B2p3/8/5N2/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.hanging_pieces(Colour::Black), vec![vec![2, 2]]);
+        assert!(game.hanging_pieces(Colour::White).is_empty());
+    }
+
+    #[test]
+    fn knight_fork() {
+        // Nc7 forks the king and the rook
+        let game = Game::from_fen("r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(game.forks(Colour::White), vec![Motif::Fork {
+            attacker: vec![1, 2],
+            targets: vec![vec![0, 0], vec![0, 4]],
+        }]);
+    }
+
+    #[test]
+    fn skewer() {
+        // The bishop skewers the king to the rook
+        let game = Game::from_fen("8/8/5r2/4k3/8/8/1B6/4K3 b - - 0 1").unwrap();
+        assert_eq!(game.skewers(Colour::White), vec![Motif::Skewer {
+            attacker: vec![6, 1],
+            front: vec![3, 4],
+            behind: vec![2, 5],
+        }]);
+    }
+
+    #[test]
+    fn mate_in_one_and_back_rank() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(game.mates_in_one(), vec![Move::parse("a1a8").unwrap()]);
+
+        // With black to move, the threat is still there
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert!(game.mates_in_one().is_empty());
+        let findings = game.find_motifs();
+        assert!(findings.contains(&Finding {
+            colour: Colour::White,
+            motif: Motif::BackRankThreat { mating_move: Move::parse("a1a8").unwrap() },
+        }));
+    }
+}
@@ -0,0 +1,315 @@
+//! An efficiently updatable neural network (NNUE) evaluation.
+//!
+//! The network has one hidden layer, kept for both players. Each input is a
+//! piece of a colour on a square, seen from one player's side: own pieces
+//! first, and the board turned around for black. The hidden values for a
+//! player, the accumulator, are the biases plus the weights of the pieces on
+//! the board, so a move only adds and removes a few weight columns. The
+//! output is
+//!
+//! ```text
+//! (output_bias + Σ clamp(mover[i], 0, 255) · w[i] + Σ clamp(other[i], 0, 255) · w[H + i]) / 64
+//! ```
+//!
+//! in centipawns for the player to move. Everything is plain integer loops,
+//! with no SIMD needed.
+//!
+//! Weight files are little-endian:
+//!
+//! ```text
+//! [u8; 4]      magic "ECNN"
+//! u32          version, 1
+//! u32          hidden size H
+//! i16 × 768·H  input weights, the H weights of input 0, then of input 1 and so on
+//! i16 × H      hidden biases
+//! i16 × 2·H    output weights, first for the player to move's half
+//! i32          output bias
+//! ```
+//!
+//! Input `side · 384 + piece · 64 + square` has side 0 for the player's own
+//! pieces and 1 for the opponent's, pieces in the order pawn, knight, bishop,
+//! rook, queen, king and squares from a8 = 0 to h1 = 63, with the ranks
+//! turned around for black.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::search::{Evaluator, IncrementalEvaluator};
+use crate::{Colour, Game, Move, Piece, PieceType};
+
+const MAGIC: &[u8; 4] = b"ECNN";
+const VERSION: u32 = 1;
+pub const INPUTS: usize = 768;
+/// Largest hidden value passed on to the output.
+const CLAMP: i32 = 255;
+const OUTPUT_DIVISOR: i32 = 64;
+
+fn piece_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::PAWN => 0,
+        PieceType::KNIGHT => 1,
+        PieceType::BISHOP => 2,
+        PieceType::ROOK => 3,
+        PieceType::QUEEN => 4,
+        PieceType::KING => 5,
+    }
+}
+
+fn colour_index(colour: Colour) -> usize {
+    match colour {
+        Colour::White => 0,
+        Colour::Black => 1,
+    }
+}
+
+/// Input of `piece` on [y, x] seen from `perspective`'s side.
+pub fn input_index(perspective: Colour, piece: Piece, square: &[usize]) -> usize {
+    let side = if piece.color == perspective { 0 } else { 1 };
+    let y = if perspective == Colour::White { square[0] } else { 7 - square[0] };
+    side * 384 + piece_index(piece.piece_type) * 64 + y * 8 + square[1]
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    hidden: usize,
+    input_weights: Vec<i16>,
+    hidden_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+/// Hidden values from white's and from black's side.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator {
+    sides: [Vec<i32>; 2],
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Network {
+    /// A network from its weights, laid out like in the file. Returns `None` if a length doesn't fit `hidden`.
+    pub fn new(hidden: usize, input_weights: Vec<i16>, hidden_biases: Vec<i16>, output_weights: Vec<i16>, output_bias: i32) -> Option<Network> {
+        if hidden == 0 || input_weights.len() != INPUTS * hidden || hidden_biases.len() != hidden || output_weights.len() != 2 * hidden {
+            return None;
+        }
+        Some(Network { hidden, input_weights, hidden_biases, output_weights, output_bias })
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Network> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() < 12 || &bytes[0..4] != MAGIC {
+            return Err(invalid("not a network file"));
+        }
+        if u32::from_le_bytes(bytes[4..8].try_into().unwrap()) != VERSION {
+            return Err(invalid("unsupported network version"));
+        }
+        let hidden = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let weights = (INPUTS + 3) * hidden;
+        if bytes.len() != 12 + weights * 2 + 4 {
+            return Err(invalid("network file has the wrong size"));
+        }
+        let mut values = bytes[12..12 + weights * 2].chunks_exact(2).map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let input_weights = values.by_ref().take(INPUTS * hidden).collect();
+        let hidden_biases = values.by_ref().take(hidden).collect();
+        let output_weights = values.collect();
+        let output_bias = i32::from_le_bytes(bytes[bytes.len() - 4..].try_into().unwrap());
+        Network::new(hidden, input_weights, hidden_biases, output_weights, output_bias).ok_or_else(|| invalid("empty network"))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Network> {
+        Network::read(File::open(path)?)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.hidden as u32).to_le_bytes())?;
+        for value in self.input_weights.iter().chain(&self.hidden_biases).chain(&self.output_weights) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&self.output_bias.to_le_bytes())
+    }
+
+    fn add(&self, values: &mut [i32], input: usize) {
+        let weights = &self.input_weights[input * self.hidden..(input + 1) * self.hidden];
+        for (value, &weight) in values.iter_mut().zip(weights) {
+            *value += weight as i32;
+        }
+    }
+
+    fn remove(&self, values: &mut [i32], input: usize) {
+        let weights = &self.input_weights[input * self.hidden..(input + 1) * self.hidden];
+        for (value, &weight) in values.iter_mut().zip(weights) {
+            *value -= weight as i32;
+        }
+    }
+
+    /// The accumulator of a position, computed from scratch.
+    pub fn accumulator(&self, game: &Game) -> Accumulator {
+        let biases: Vec<i32> = self.hidden_biases.iter().map(|&bias| bias as i32).collect();
+        let mut accumulator = Accumulator { sides: [biases.clone(), biases] };
+        for (y, row) in game.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                if let Some(piece) = square {
+                    for perspective in [Colour::White, Colour::Black] {
+                        self.add(&mut accumulator.sides[colour_index(perspective)], input_index(perspective, *piece, &[y, x]));
+                    }
+                }
+            }
+        }
+        accumulator
+    }
+
+    /// The accumulator after `m` is played in `game`, whose accumulator is `before`.
+    pub fn update(&self, before: &Accumulator, game: &Game, m: &Move) -> Accumulator {
+        let mut after = before.clone();
+        let Some(piece) = game.board[m.from[0]][m.from[1]] else { return after };
+        let captured = game.board[m.to[0]][m.to[1]];
+        // Promotion like in `search::play`
+        let placed = match game.is_promotion(&m.from, &m.to) {
+            true => Piece { color: piece.color, piece_type: m.promotion.unwrap_or(game.promotion_type) },
+            false => piece,
+        };
+        for perspective in [Colour::White, Colour::Black] {
+            let values = &mut after.sides[colour_index(perspective)];
+            self.remove(values, input_index(perspective, piece, &m.from));
+            if let Some(captured) = captured {
+                self.remove(values, input_index(perspective, captured, &m.to));
+            }
+            self.add(values, input_index(perspective, placed, &m.to));
+        }
+        after
+    }
+
+    /// Score for the player to move in a position with the given accumulator.
+    pub fn output(&self, accumulator: &Accumulator, to_move: Colour) -> i32 {
+        let mover = &accumulator.sides[colour_index(to_move)];
+        let other = &accumulator.sides[colour_index(to_move.opposite())];
+        let mut sum = self.output_bias as i64;
+        for (value, &weight) in mover.iter().chain(other).zip(&self.output_weights) {
+            sum += (*value).clamp(0, CLAMP) as i64 * weight as i64;
+        }
+        (sum / OUTPUT_DIVISOR as i64) as i32
+    }
+}
+
+impl Evaluator for Network {
+    fn evaluate(&self, game: &Game) -> i32 {
+        self.output(&self.accumulator(game), game.active_colour)
+    }
+
+    fn incremental(&self, root: &Game) -> Box<dyn IncrementalEvaluator + '_> {
+        Box::new(NetworkState { network: self, stack: vec![self.accumulator(root)] })
+    }
+}
+
+/// Accumulators of the positions on the way from the root to the one searched.
+struct NetworkState<'a> {
+    network: &'a Network,
+    stack: Vec<Accumulator>,
+}
+
+impl IncrementalEvaluator for NetworkState<'_> {
+    fn make_move(&mut self, game: &Game, m: &Move) {
+        let after = self.network.update(self.stack.last().unwrap(), game, m);
+        self.stack.push(after);
+    }
+
+    fn undo_move(&mut self) {
+        if self.stack.len() > 1 {
+            self.stack.pop();
+        }
+    }
+
+    fn evaluate(&mut self, game: &Game) -> i32 {
+        self.network.output(self.stack.last().unwrap(), game.active_colour)
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{input_index, Network, INPUTS};
+    use crate::search::{play, search_with_evaluator, Evaluator, SearchOptions};
+    use crate::zobrist::splitmix64;
+    use crate::{Colour, Game, Move, PieceType};
+
+    /// Two hidden values, the player's own material and the opponent's, in units of 20 centipawns.
+    fn material_network() -> Network {
+        let mut input_weights = vec![0; INPUTS * 2];
+        for input in 0..INPUTS {
+            let piece_type = [PieceType::PAWN, PieceType::KNIGHT, PieceType::BISHOP, PieceType::ROOK, PieceType::QUEEN, PieceType::KING][input / 64 % 6];
+            let value = if piece_type == PieceType::KING { 0 } else { piece_type.value() as i16 / 20 };
+            input_weights[input * 2 + input / 384] = value;
+        }
+        Network::new(2, input_weights, vec![0, 0], vec![10 * 64, -10 * 64, -10 * 64, 10 * 64], 0).unwrap()
+    }
+
+    fn random_network(seed: u64) -> Network {
+        let mut state = seed;
+        let mut random = |count: usize| (0..count).map(|_| (splitmix64(&mut state) % 61) as i16 - 30).collect::<Vec<i16>>();
+        Network::new(16, random(INPUTS * 16), random(16), random(32), 100).unwrap()
+    }
+
+    #[test]
+    fn material() {
+        let network = material_network();
+        assert_eq!(network.evaluate(&Game::new()), 0);
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(network.evaluate(&game), 900);
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(network.evaluate(&game), -900);
+
+        // The network plays like the material count
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = search_with_evaluator(&game, &SearchOptions { depth: 2, hash_mb: 1, ..SearchOptions::default() }, &network);
+        assert_eq!(result.best_move, Move::parse("d2d5"));
+    }
+
+    #[test]
+    fn updates_match_refresh() {
+        let network = random_network(7);
+        let mut game = Game::from_fen("r3k3/1P6/8/3q4/4N3/8/6p1/4K3 w - - 0 1").unwrap();
+        let mut accumulator = network.accumulator(&game);
+        // A capture with promotion, quiet moves and an underpromotion for black
+        for m in ["b7a8q", "e8e7", "e4c5", "g2g1n"] {
+            let m = Move::parse(m).unwrap();
+            assert!(game.legal_moves().contains(&m));
+            accumulator = network.update(&accumulator, &game, &m);
+            game = play(&game, &m);
+            assert_eq!(accumulator, network.accumulator(&game));
+        }
+        assert_eq!(network.output(&accumulator, game.active_colour), network.evaluate(&game));
+    }
+
+    #[test]
+    fn inputs_are_mirrored_for_black() {
+        let game = Game::new();
+        let white_king = game.board[7][4].unwrap();
+        let black_king = game.board[0][4].unwrap();
+        assert_eq!(input_index(Colour::White, white_king, &[7, 4]), input_index(Colour::Black, black_king, &[0, 4]));
+        // The start position looks the same to both players
+        let accumulator = random_network(3).accumulator(&game);
+        assert_eq!(accumulator.sides[0], accumulator.sides[1]);
+    }
+
+    #[test]
+    fn read_and_write() {
+        let network = random_network(1);
+        let mut bytes = vec![];
+        network.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 12 + (INPUTS + 3) * 16 * 2 + 4);
+        assert_eq!(Network::read(bytes.as_slice()).unwrap(), network);
+
+        assert!(Network::read(&bytes[..bytes.len() - 1]).is_err());
+        bytes[0] = b'X';
+        assert!(Network::read(bytes.as_slice()).is_err());
+    }
+}
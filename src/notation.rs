@@ -0,0 +1,247 @@
+//! Text notation for squares, moves and positions.
+//!
+//! Squares are written the usual way (`e4`), with [0, 0] being a8 and [7, 7] h1.
+//! Moves use coordinate notation (`e2e4`), with the piece a pawn is promoted
+//! to after it (`e7e8n`).
+
+use std::fmt;
+
+use crate::{Colour, Game, Move, Piece, PieceType};
+
+/// FEN of the position `Game::new` sets up. Castling isn't supported by `Game`,
+/// so the castling field is always `-`.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1";
+
+/// Name of a square, e.g. [6, 4] is `e2`.
+pub fn square_name(position: &[usize]) -> String {
+    let file = (b'a' + position[1] as u8) as char;
+    let rank = 8 - position[0];
+    format!("{}{}", file, rank)
+}
+
+/// Parses a square name like `e2` into [y, x].
+pub fn parse_square(name: &str) -> Option<Vec<usize>> {
+    let bytes = name.as_bytes();
+    if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+        return None;
+    }
+    Some(vec![(b'8' - bytes[1]) as usize, (bytes[0] - b'a') as usize])
+}
+
+impl Move {
+    /// Parses a move in coordinate notation, e.g. `e2e4` or `e7e8q`.
+    pub fn parse(text: &str) -> Option<Move> {
+        if !(4..=5).contains(&text.len()) || !text.is_ascii() {
+            return None;
+        }
+        let promotion = match text[4..].chars().next() {
+            Some(c) => Some(Piece::from_char(c).filter(|piece| crate::PROMOTION_TYPES.contains(&piece.piece_type))?.piece_type),
+            None => None,
+        };
+        Some(Move {
+            from: parse_square(&text[0..2])?,
+            to: parse_square(&text[2..4])?,
+            promotion,
+        })
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", square_name(&self.from), square_name(&self.to))?;
+        if let Some(piece_type) = self.promotion {
+            write!(f, "{}", Piece { color: Colour::Black, piece_type }.to_char())?;
+        }
+        Ok(())
+    }
+}
+
+impl Piece {
+    /// FEN letter of the piece, upper case for white.
+    pub fn to_char(&self) -> char {
+        let c = match self.piece_type {
+            PieceType::PAWN => 'p',
+            PieceType::ROOK => 'r',
+            PieceType::BISHOP => 'b',
+            PieceType::KNIGHT => 'n',
+            PieceType::QUEEN => 'q',
+            PieceType::KING => 'k',
+        };
+        match self.color {
+            Colour::White => c.to_ascii_uppercase(),
+            Colour::Black => c,
+        }
+    }
+
+    /// Reads a FEN piece letter.
+    pub fn from_char(c: char) -> Option<Piece> {
+        let piece_type = match c.to_ascii_lowercase() {
+            'p' => PieceType::PAWN,
+            'r' => PieceType::ROOK,
+            'b' => PieceType::BISHOP,
+            'n' => PieceType::KNIGHT,
+            'q' => PieceType::QUEEN,
+            'k' => PieceType::KING,
+            _ => return None,
+        };
+        let color = if c.is_ascii_uppercase() { Colour::White } else { Colour::Black };
+        Some(Piece { color, piece_type })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FenError {
+    MissingField,
+    InvalidPlacement,
+    InvalidActiveColour,
+    InvalidMoveNumber,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            FenError::MissingField => "missing field",
+            FenError::InvalidPlacement => "invalid piece placement",
+            FenError::InvalidActiveColour => "invalid active colour",
+            FenError::InvalidMoveNumber => "invalid move number",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Reads the piece placement field of a FEN.
+fn parse_placement(placement: &str) -> Result<Vec<Vec<Option<Piece>>>, FenError> {
+    let mut board = vec![];
+    for rank in placement.split('/') {
+        let mut row = vec![];
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                row.extend(std::iter::repeat_n(None, empty as usize));
+            }
+            else {
+                row.push(Some(Piece::from_char(c).ok_or(FenError::InvalidPlacement)?));
+            }
+        }
+        if row.len() != 8 {
+            return Err(FenError::InvalidPlacement);
+        }
+        board.push(row);
+    }
+    if board.len() != 8 {
+        return Err(FenError::InvalidPlacement);
+    }
+    Ok(board)
+}
+
+impl Game {
+    /// Sets up a game from a FEN string. Castling rights and en passant square are
+    /// read but ignored since `Game` doesn't support those moves. The move counters
+    /// may be left out, as in EPD.
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 2 {
+            return Err(FenError::MissingField);
+        }
+
+        let mut game = Game::new();
+        game.board = parse_placement(fields[0])?;
+        game.active_colour = match fields[1] {
+            "w" => Colour::White,
+            "b" => Colour::Black,
+            _ => return Err(FenError::InvalidActiveColour),
+        };
+        if let Some(halfmove) = fields.get(4) {
+            game.halfmove_clock = halfmove.parse().map_err(|_| FenError::InvalidMoveNumber)?;
+        }
+        if let Some(fullmove) = fields.get(5) {
+            game.fullmove_number = fullmove.parse().map_err(|_| FenError::InvalidMoveNumber)?;
+        }
+        game.start_fen = game.to_fen();
+        Ok(game)
+    }
+
+    /// FEN of the current position.
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for (y, row) in self.board.iter().enumerate() {
+            if y > 0 {
+                placement.push('/');
+            }
+            let mut empty = 0;
+            for square in row {
+                match square {
+                    Some(piece) => {
+                        if empty > 0 {
+                            placement += &empty.to_string();
+                            empty = 0;
+                        }
+                        placement.push(piece.to_char());
+                    },
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement += &empty.to_string();
+            }
+        }
+
+        let colour = match self.active_colour {
+            Colour::White => "w",
+            Colour::Black => "b",
+        };
+        format!("{} {} - - {} {}", placement, colour, self.halfmove_clock, self.fullmove_number)
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_square, square_name, FenError, STARTING_FEN};
+    use crate::{Colour, Game, Move, Piece, PieceType};
+
+    #[test]
+    fn squares() {
+        assert_eq!(square_name(&[6, 4]), "e2");
+        assert_eq!(parse_square("a8"), Some(vec![0, 0]));
+        assert_eq!(parse_square("h1"), Some(vec![7, 7]));
+        assert_eq!(parse_square("i1"), None);
+        assert_eq!(Move::parse("e2e4"), Some(Move::new(vec![6, 4], vec![4, 4])));
+        let promotion = Move::parse("e7e8n").unwrap();
+        assert_eq!(promotion.promotion, Some(PieceType::KNIGHT));
+        assert_eq!(promotion.to_string(), "e7e8n");
+        assert_eq!(Move::parse("e7e8k"), None);
+        assert_eq!(Move::parse("e2e4").unwrap().to_string(), "e2e4");
+    }
+
+    #[test]
+    fn fen_of_new_game() {
+        assert_eq!(Game::new().to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn fen_after_moves() {
+        let mut game = Game::new();
+        game.make_move(vec![6,4], vec![4,4]);
+        game.make_move(vec![1,2], vec![3,2]);
+        game.make_move(vec![7,6], vec![5,5]);
+        assert_eq!(game.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b - - 1 2");
+    }
+
+    #[test]
+    fn read_fen() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b KQkq - 3 40").unwrap();
+        assert_eq!(game.active_colour, Colour::Black);
+        assert_eq!(game.board[6][4], Some(Piece { color: Colour::White, piece_type: PieceType::PAWN }));
+        assert_eq!(game.fullmove_number, 40);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 b - - 3 40");
+
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/4P3/4K3").err(), Some(FenError::MissingField));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/4P3/4K4 w").err(), Some(FenError::InvalidPlacement));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 x").err(), Some(FenError::InvalidActiveColour));
+    }
+}
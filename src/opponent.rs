@@ -0,0 +1,150 @@
+//! Computer opponents of adjustable strength and playing style.
+//!
+//! Weaker opponents search less deep, give up after fewer nodes and pick at
+//! random among the moves that are not much worse than the best one.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::search::{Engine, Score, SearchOptions, Style, MATE};
+use crate::zobrist::splitmix64;
+use crate::{Game, GameState, Move};
+
+/// Lines considered when the opponent may play a worse move.
+const CANDIDATES: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strength {
+    pub depth: u32,
+    pub max_nodes: Option<u64>,
+    /// How many centipawns worse than the best move a chosen move may be.
+    pub randomness: i32,
+}
+
+impl Strength {
+    pub const MAX_LEVEL: u8 = 20;
+
+    /// Level 0 to `MAX_LEVEL`, the highest is full strength.
+    pub fn level(level: u8) -> Strength {
+        let level = level.min(Strength::MAX_LEVEL);
+        Strength {
+            depth: 1 + level as u32 * 3 / Strength::MAX_LEVEL as u32,
+            max_nodes: (level < Strength::MAX_LEVEL).then(|| 500 << (level / 2)),
+            randomness: (Strength::MAX_LEVEL - level) as i32 * 20,
+        }
+    }
+
+    /// A level for a player rated `elo`, from 800 for level 0 up to 2400 for
+    /// full strength. The ratings are a rough guide, not measured.
+    pub fn elo(elo: u32) -> Strength {
+        Strength::level(((elo.clamp(800, 2400) - 800) / 80) as u8)
+    }
+}
+
+impl Default for Strength {
+    fn default() -> Strength {
+        Strength::level(Strength::MAX_LEVEL)
+    }
+}
+
+/// Mates come before every other score, the closest first.
+fn centipawns(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(centipawns) => centipawns,
+        Score::Mate(moves) if moves > 0 => MATE - 2 * moves,
+        Score::Mate(moves) => -MATE - 2 * moves,
+    }
+}
+
+pub struct Opponent {
+    pub strength: Strength,
+    pub style: Style,
+    engine: Engine,
+    random: u64,
+}
+
+impl Opponent {
+    pub fn new(strength: Strength, style: Style) -> Opponent {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        Opponent::with_seed(strength, style, seed)
+    }
+
+    /// An opponent that makes the same choices every time for the same seed.
+    pub fn with_seed(strength: Strength, style: Style, seed: u64) -> Opponent {
+        Opponent { strength, style, engine: Engine::default(), random: seed }
+    }
+
+    /// Forgets what was learned while thinking about earlier moves, for a new game.
+    pub fn new_game(&mut self) {
+        self.engine.clear();
+    }
+
+    pub fn choose_move(&mut self, game: &Game) -> Option<Move> {
+        self.engine.options = SearchOptions {
+            depth: self.strength.depth,
+            max_nodes: self.strength.max_nodes,
+            multi_pv: if self.strength.randomness > 0 { CANDIDATES } else { 1 },
+            style: self.style,
+            ..self.engine.options.clone()
+        };
+        let result = self.engine.search(game);
+
+        // Each line gets up to `randomness` added, so a line further behind than that is never chosen
+        let random = &mut self.random;
+        let randomness = self.strength.randomness as u64;
+        result.lines.into_iter()
+            .max_by_key(|line| centipawns(line.score) + (splitmix64(random) % (randomness + 1)) as i32)
+            .and_then(|line| line.pv.into_iter().next())
+    }
+
+    /// Chooses a move and plays it.
+    pub fn play(&mut self, game: &mut Game) -> Option<GameState> {
+        let m = self.choose_move(game)?;
+        game.play_move(&m)
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Opponent, Strength};
+    use crate::search::{search, Style};
+    use crate::{Game, Move};
+
+    #[test]
+    fn levels() {
+        assert_eq!(Strength::level(20), Strength { depth: 4, max_nodes: None, randomness: 0 });
+        assert_eq!(Strength::level(0), Strength { depth: 1, max_nodes: Some(500), randomness: 400 });
+        assert_eq!(Strength::elo(3000), Strength::default());
+        assert_eq!(Strength::elo(1600), Strength::level(10));
+    }
+
+    #[test]
+    fn full_strength_plays_the_best_move() {
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let mut opponent = Opponent::with_seed(Strength::default(), Style::default(), 1);
+        assert_eq!(opponent.choose_move(&game), search(&game, 4).best_move);
+    }
+
+    #[test]
+    fn weak_opponent_varies_but_not_too_much() {
+        let game = Game::new();
+        let strength = Strength { depth: 2, max_nodes: None, randomness: 60 };
+        let moves: Vec<Move> = (0..8)
+            .map(|seed| Opponent::with_seed(strength.clone(), Style::default(), seed).choose_move(&game).unwrap())
+            .collect();
+        assert!(moves.iter().any(|m| *m != moves[0]));
+        // The same seed makes the same choice
+        let mut opponent = Opponent::with_seed(strength.clone(), Style::default(), 3);
+        assert_eq!(opponent.choose_move(&game).as_ref(), Some(&moves[3]));
+
+        // A queen is too much to give away
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        for seed in 0..8 {
+            let mut opponent = Opponent::with_seed(strength.clone(), Style::default(), seed);
+            assert_eq!(opponent.choose_move(&game), Move::parse("d2d5"));
+        }
+    }
+}
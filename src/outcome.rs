@@ -0,0 +1,97 @@
+//! The squares a move changed, for animating it.
+//!
+//! `Game` has no castling or en passant, so a move changes at most the two
+//! squares it is played between.
+
+use crate::{Game, GameState, Piece};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardChange {
+    /// `piece` went from `from` to `to`.
+    Relocated { piece: Piece, from: Vec<usize>, to: Vec<usize> },
+    /// `piece` was taken off `square`.
+    Removed { piece: Piece, square: Vec<usize> },
+    /// `old` on `square` became `new`, as when a pawn is promoted.
+    Replaced { square: Vec<usize>, old: Piece, new: Piece },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveOutcome {
+    pub state: GameState,
+    /// In the order they should be shown. Empty if the game was already over.
+    pub changes: Vec<BoardChange>,
+}
+
+impl Game {
+    /// Like `make_move`, but also says how the board changed.
+    pub fn make_move_with_outcome(&mut self, from: Vec<usize>, to: Vec<usize>) -> Option<MoveOutcome> {
+        let piece = self.board[from[0]][from[1]];
+        let captured = self.board[to[0]][to[1]];
+        let moves_made = self.history.len();
+
+        let state = self.make_move(from.clone(), to.clone())?;
+        if self.history.len() == moves_made {
+            return Some(MoveOutcome { state, changes: vec![] });
+        }
+
+        // A move was made, so there was a piece to move
+        let piece = piece?;
+        let mut changes = vec![];
+        if let Some(captured) = captured {
+            changes.push(BoardChange::Removed { piece: captured, square: to.clone() });
+        }
+        changes.push(BoardChange::Relocated { piece, from, to: to.clone() });
+        if let Some(new) = self.board[to[0]][to[1]].filter(|new| *new != piece) {
+            changes.push(BoardChange::Replaced { square: to, old: piece, new });
+        }
+        Some(MoveOutcome { state, changes })
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{BoardChange, MoveOutcome};
+    use crate::{Colour, Game, GameState, Piece, PieceType};
+
+    #[test]
+    fn quiet_move() {
+        let mut game = Game::new();
+        assert_eq!(game.make_move_with_outcome(vec![6, 4], vec![4, 4]), Some(MoveOutcome {
+            state: GameState::InProgress,
+            changes: vec![BoardChange::Relocated {
+                piece: Piece { color: Colour::White, piece_type: PieceType::PAWN },
+                from: vec![6, 4],
+                to: vec![4, 4],
+            }],
+        }));
+        assert_eq!(game.make_move_with_outcome(vec![6, 4], vec![4, 4]), None);
+    }
+
+    #[test]
+    fn capture_with_promotion() {
+        let mut game = Game::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let pawn = Piece { color: Colour::White, piece_type: PieceType::PAWN };
+        let queen = Piece { color: Colour::White, piece_type: PieceType::QUEEN };
+        let outcome = game.make_move_with_outcome(vec![1, 0], vec![0, 1]).unwrap();
+        assert_eq!(outcome.state, GameState::Check);
+        assert_eq!(outcome.changes, vec![
+            BoardChange::Removed { piece: Piece { color: Colour::Black, piece_type: PieceType::ROOK }, square: vec![0, 1] },
+            BoardChange::Relocated { piece: pawn, from: vec![1, 0], to: vec![0, 1] },
+            BoardChange::Replaced { square: vec![0, 1], old: pawn, new: queen },
+        ]);
+    }
+
+    #[test]
+    fn finished_game_changes_nothing() {
+        let mut game = Game::new();
+        game.resign(Colour::White);
+        assert_eq!(game.make_move_with_outcome(vec![1, 4], vec![3, 4]), Some(MoveOutcome {
+            state: GameState::GameOver,
+            changes: vec![],
+        }));
+    }
+}
@@ -0,0 +1,278 @@
+//! Reading games in PGN.
+//!
+//! Moves are replayed through `Game::make_move`. Castling isn't supported by
+//! `Game`, so a game stops at its first castling move (or any other move that
+//! can't be played) and the rest of its moves are skipped.
+
+use crate::notation::parse_square;
+use crate::{Colour, Game, GameResult, Move, PieceType};
+
+pub struct PgnGame {
+    /// Tag pairs in the order they were given.
+    pub tags: Vec<(String, String)>,
+    /// The game after replaying every playable move.
+    pub game: Game,
+    pub result: Option<GameResult>,
+    /// The first move that couldn't be played, if any.
+    pub unplayable: Option<String>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// The piece a pawn is promoted to in a move like `e8=N` or `e8N`.
+fn promotion_piece(san: &str) -> Option<PieceType> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    if !san.chars().rev().nth(1).is_some_and(|c| c == '=' || c.is_ascii_digit()) {
+        return None;
+    }
+    match san.chars().last()? {
+        'Q' => Some(PieceType::QUEEN),
+        'R' => Some(PieceType::ROOK),
+        'B' => Some(PieceType::BISHOP),
+        'N' => Some(PieceType::KNIGHT),
+        _ => None,
+    }
+}
+
+fn parse_result(text: &str) -> Option<Option<GameResult>> {
+    match text {
+        "1-0" => Some(Some(GameResult::Win(Colour::White))),
+        "0-1" => Some(Some(GameResult::Win(Colour::Black))),
+        "1/2-1/2" => Some(Some(GameResult::Draw)),
+        "*" => Some(None),
+        _ => None,
+    }
+}
+
+impl Game {
+    /// Finds the legal move written in standard algebraic notation, e.g. `Nf3` or `exd5`.
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        // A promotion without a piece is to a queen
+        let promotion = promotion_piece(san).unwrap_or(PieceType::QUEEN);
+        let san = match san.find('=') {
+            Some(index) => &san[..index],
+            None => san.trim_end_matches(['Q', 'R', 'B', 'N']),
+        };
+
+        let mut chars = san.chars();
+        let piece_type = match chars.next()? {
+            'K' => PieceType::KING,
+            'Q' => PieceType::QUEEN,
+            'R' => PieceType::ROOK,
+            'B' => PieceType::BISHOP,
+            'N' => PieceType::KNIGHT,
+            _ => {
+                chars = san.chars();
+                PieceType::PAWN
+            },
+        };
+        let rest: String = chars.filter(|c| *c != 'x').collect();
+        if rest.len() < 2 || !rest.is_ascii() {
+            return None;
+        }
+        let to = parse_square(&rest[rest.len() - 2..])?;
+
+        // What's left is the file and/or rank of the piece that moves
+        let mut file = None;
+        let mut rank = None;
+        for c in rest[..rest.len() - 2].chars() {
+            match c {
+                'a'..='h' => file = Some(c as usize - 'a' as usize),
+                '1'..='8' => rank = Some('8' as usize - c as usize),
+                _ => return None,
+            }
+        }
+
+        let mut candidates = self.legal_moves().into_iter().filter(|m| {
+            let piece = self.board[m.from[0]][m.from[1]].unwrap();
+            piece.piece_type == piece_type
+                && m.to == to
+                && file.is_none_or(|file| m.from[1] == file)
+                && rank.is_none_or(|rank| m.from[0] == rank)
+                && m.promotion.is_none_or(|piece_type| piece_type == promotion)
+        });
+        let found = candidates.next()?;
+        if candidates.next().is_some() {
+            // Ambiguous
+            return None;
+        }
+        Some(found)
+    }
+}
+
+/// Tags and move text of one game before it's replayed.
+#[derive(Default)]
+struct RawGame {
+    tags: Vec<(String, String)>,
+    moves: Vec<String>,
+    result: Option<Option<GameResult>>,
+}
+
+impl RawGame {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.moves.is_empty() && self.result.is_none()
+    }
+
+    fn replay(self) -> PgnGame {
+        let fen = self.tags.iter().find(|(key, _)| key == "FEN").map(|(_, value)| value.as_str());
+        let mut game = match fen.map(Game::from_fen) {
+            Some(Ok(game)) => game,
+            _ => Game::new(),
+        };
+
+        let mut unplayable = None;
+        for san in self.moves {
+            let played = match game.parse_san(&san) {
+                Some(m) => game.play_move(&m).is_some(),
+                None => false,
+            };
+            if !played {
+                unplayable = Some(san);
+                break;
+            }
+        }
+
+        let result = match self.result {
+            Some(result) => result,
+            None => self.tags.iter()
+                .find(|(key, _)| key == "Result")
+                .and_then(|(_, value)| parse_result(value))
+                .flatten(),
+        };
+        PgnGame { tags: self.tags, game, result, unplayable }
+    }
+}
+
+/// Reads a tag pair like `Event "Club championship"` (without brackets).
+fn parse_tag(text: &str) -> Option<(String, String)> {
+    let (name, value) = text.trim().split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+/// Reads every game in a PGN collection.
+pub fn read_games(text: &str) -> Vec<PgnGame> {
+    let mut games = vec![];
+    let mut current = RawGame::default();
+    let mut chars = text.chars();
+    let mut token = String::new();
+
+    // Ends the token being read, which may finish the current game
+    let end_token = |token: &mut String, current: &mut RawGame, games: &mut Vec<PgnGame>| {
+        if token.is_empty() {
+            return;
+        }
+        if let Some(result) = parse_result(token) {
+            current.result = Some(result);
+            games.push(std::mem::take(current).replay());
+        }
+        else {
+            // Skip move numbers, which may be written together with the move (1.e4)
+            let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+            if !san.is_empty() && !san.starts_with('$') {
+                current.moves.push(san.to_string());
+            }
+        }
+        token.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                end_token(&mut token, &mut current, &mut games);
+                // Tags after the move text belong to the next game
+                if !current.moves.is_empty() {
+                    games.push(std::mem::take(&mut current).replay());
+                }
+                let tag: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                if let Some(tag) = parse_tag(&tag) {
+                    current.tags.push(tag);
+                }
+            },
+            '{' => {
+                end_token(&mut token, &mut current, &mut games);
+                chars.by_ref().take_while(|c| *c != '}').for_each(drop);
+            },
+            ';' => {
+                end_token(&mut token, &mut current, &mut games);
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            },
+            '(' => {
+                // Variations are skipped
+                end_token(&mut token, &mut current, &mut games);
+                let mut depth = 1;
+                for c in chars.by_ref() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {},
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            },
+            c if c.is_whitespace() => end_token(&mut token, &mut current, &mut games),
+            c => token.push(c),
+        }
+    }
+    end_token(&mut token, &mut current, &mut games);
+    if !current.is_empty() {
+        games.push(current.replay());
+    }
+    games
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::read_games;
+    use crate::{Colour, Game, GameResult, Move};
+
+    #[test]
+    fn parse_san() {
+        let game = Game::from_fen("4k3/8/8/3p4/4P3/8/8/R3K2R w - - 0 1").unwrap();
+        assert_eq!(game.parse_san("exd5"), Move::parse("e4d5"));
+        assert_eq!(game.parse_san("e5"), Move::parse("e4e5"));
+        assert_eq!(game.parse_san("Rad1"), Move::parse("a1d1"));
+        assert_eq!(game.parse_san("Ke2+"), Move::parse("e1e2"));
+        assert_eq!(game.parse_san("Nf3"), None);
+
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+        assert_eq!(game.parse_san("Nd2"), None);
+        assert_eq!(game.parse_san("Nbd2"), Move::parse("b1d2"));
+        assert_eq!(game.parse_san("N1xd2"), None);
+    }
+
+    #[test]
+    fn read_collection() {
+        let text = r#"
+[Event "Club"]
+[White "A"]
+[Result "1-0"]
+
+1. e4 e5 {King's pawn} 2. Nf3 (2. f4 exf4) Nc6 3.Bb5 a6 4. Ba4 Nf6 5. O-O Be7 1-0
+
+[Event "Club"]
+[Result "*"]
+
+1. d4 d5 *
+"#;
+        let games = read_games(text);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(games[0].result, Some(GameResult::Win(Colour::White)));
+        assert_eq!(games[0].game.history.len(), 8);
+        assert_eq!(games[0].unplayable.as_deref(), Some("O-O"));
+        assert_eq!(games[1].result, None);
+        assert_eq!(games[1].game.to_fen(), "rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w - - 0 2");
+    }
+}
@@ -0,0 +1,197 @@
+//! Exact solver for composed problems: mate, helpmate and selfmate in N moves.
+//!
+//! N counts the moves of the player to move. Every key move is returned, so a
+//! problem with more than one solution (a cook) is easy to spot.
+
+use crate::search::play;
+use crate::{Game, Move};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stipulation {
+    /// The player to move mates against any defence.
+    Mate,
+    /// Both players work together for the player to move to get mated.
+    Helpmate,
+    /// The player to move forces the opponent to mate them.
+    Selfmate,
+}
+
+/// A move and what follows it. After the attacker's moves every defence is
+/// listed, after a defence the attacker's move that keeps the solution going.
+/// In a helpmate every way of going on is listed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variation {
+    pub played: Move,
+    pub replies: Vec<Variation>,
+}
+
+/// The player to move is mated, given their legal moves.
+fn is_mated(game: &Game, moves: &[Move]) -> bool {
+    moves.is_empty() && game.is_in_check(game.active_colour)
+}
+
+/// Defences after the attacker's move, if all of them lose to a mate in `n` more moves.
+fn mate_defences(game: &Game, n: u32) -> Option<Vec<Variation>> {
+    let defences = game.legal_moves();
+    if defences.is_empty() {
+        return if is_mated(game, &defences) { Some(vec![]) } else { None };
+    }
+    if n == 0 {
+        return None;
+    }
+    defences.into_iter()
+        .map(|defence| {
+            let after = play(game, &defence);
+            let attack = after.legal_moves().into_iter()
+                .find_map(|m| mate_defences(&play(&after, &m), n - 1).map(|replies| Variation { played: m, replies }))?;
+            Some(Variation { played: defence, replies: vec![attack] })
+        })
+        .collect()
+}
+
+/// Ways for the player to move to get mated, `n` moves each.
+fn helpmates(game: &Game, n: u32) -> Vec<Variation> {
+    let mut solutions = vec![];
+    for m in game.legal_moves() {
+        let after = play(game, &m);
+        let replies: Vec<Variation> = after.legal_moves().into_iter()
+            .filter_map(|reply| {
+                let end = play(&after, &reply);
+                let moves = end.legal_moves();
+                if n == 1 {
+                    return is_mated(&end, &moves).then(|| Variation { played: reply, replies: vec![] });
+                }
+                if moves.is_empty() {
+                    return None;
+                }
+                let rest = helpmates(&end, n - 1);
+                (!rest.is_empty()).then_some(Variation { played: reply, replies: rest })
+            })
+            .collect();
+        if !replies.is_empty() {
+            solutions.push(Variation { played: m, replies });
+        }
+    }
+    solutions
+}
+
+/// Defences after the attacker's move, if all of them either mate the
+/// attacker or lose to a selfmate in `n` more moves.
+fn selfmate_defences(game: &Game, n: u32) -> Option<Vec<Variation>> {
+    let defences = game.legal_moves();
+    if defences.is_empty() {
+        // The defender is mated or stalemated, which isn't the aim
+        return None;
+    }
+    defences.into_iter()
+        .map(|defence| {
+            let after = play(game, &defence);
+            let moves = after.legal_moves();
+            if is_mated(&after, &moves) {
+                return Some(Variation { played: defence, replies: vec![] });
+            }
+            if n == 0 {
+                return None;
+            }
+            let attack = moves.into_iter()
+                .find_map(|m| selfmate_defences(&play(&after, &m), n - 1).map(|replies| Variation { played: m, replies }))?;
+            Some(Variation { played: defence, replies: vec![attack] })
+        })
+        .collect()
+}
+
+impl Game {
+    /// Every solution of the problem in `n` moves, starting with its key move.
+    pub fn solve(&self, stipulation: Stipulation, n: u32) -> Vec<Variation> {
+        if n == 0 {
+            return vec![];
+        }
+        // The history isn't needed and would be copied in every position
+        let mut root = self.clone();
+        root.history.clear();
+
+        match stipulation {
+            Stipulation::Mate => root.legal_moves().into_iter()
+                .filter_map(|m| mate_defences(&play(&root, &m), n - 1).map(|replies| Variation { played: m, replies }))
+                .collect(),
+            Stipulation::Helpmate => helpmates(&root, n),
+            Stipulation::Selfmate => root.legal_moves().into_iter()
+                .filter_map(|m| selfmate_defences(&play(&root, &m), n - 1).map(|replies| Variation { played: m, replies }))
+                .collect(),
+        }
+    }
+
+    /// The key moves of a mate in `n`.
+    pub fn mate_in(&self, n: u32) -> Vec<Move> {
+        self.solve(Stipulation::Mate, n).into_iter().map(|variation| variation.played).collect()
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Stipulation, Variation};
+    use crate::{Game, Move};
+
+    fn m(text: &str) -> Move {
+        Move::parse(text).unwrap()
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(game.solve(Stipulation::Mate, 1), vec![Variation { played: m("a1a8"), replies: vec![] }]);
+    }
+
+    #[test]
+    fn mate_in_two() {
+        // 1. Kc7 Ka7 2. Ra1, or the rook leaves the b-file, 1... Kb8 and it mates on the back rank
+        let game = Game::from_fen("k7/8/1K6/8/8/8/8/1R6 w - - 0 1").unwrap();
+        assert!(game.mate_in(1).is_empty());
+        let solutions = game.solve(Stipulation::Mate, 2);
+        let keys: Vec<Move> = solutions.iter().map(|variation| variation.played.clone()).collect();
+        assert_eq!(keys, ["b6c7", "b1d1", "b1e1", "b1f1", "b1g1", "b1h1"].map(m));
+        for key in &solutions {
+            assert!(!key.replies.is_empty());
+            for defence in &key.replies {
+                assert_eq!(defence.replies.len(), 1);
+                let mut after = game.clone();
+                after.play_move(&key.played);
+                after.play_move(&defence.played);
+                after.play_move(&defence.replies[0].played);
+                assert!(after.is_checkmate());
+            }
+        }
+    }
+
+    #[test]
+    fn underpromotion_key() {
+        // Only a knight checks the king on h6, and the rook on a7 covers g7 and h7
+        let game = Game::from_fen("8/R5P1/6pk/6pp/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(game.mate_in(1), vec![m("g7g8n")]);
+    }
+
+    #[test]
+    fn helpmate_in_one() {
+        // Black helps by blocking the king's last square, a7
+        let game = Game::from_fen("k7/8/2K5/4B3/8/8/8/r6R b - - 0 1").unwrap();
+        let solutions = game.solve(Stipulation::Helpmate, 1);
+        assert!(solutions.contains(&Variation {
+            played: m("a1a7"),
+            replies: vec![Variation { played: m("h1h8"), replies: vec![] }],
+        }));
+    }
+
+    #[test]
+    fn selfmate_in_one() {
+        // 1. Qc8+ and black's only move Rxc8 mates
+        let game = Game::from_fen("1k1K4/5r2/2r5/Q7/8/7Q/8/8 w - - 0 1").unwrap();
+        assert_eq!(game.solve(Stipulation::Selfmate, 1), vec![Variation {
+            played: m("h3c8"),
+            replies: vec![Variation { played: m("c6c8"), replies: vec![] }],
+        }]);
+    }
+}
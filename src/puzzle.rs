@@ -0,0 +1,250 @@
+//! Tactics puzzles: a position and the line that solves it.
+//!
+//! The player makes every other move of the line and the opponent's replies
+//! are played automatically. A move that mates is always accepted, even if
+//! the line continues differently.
+
+use std::fmt;
+
+use crate::notation::FenError;
+use crate::{Game, Move};
+
+#[derive(Debug, PartialEq)]
+pub enum PuzzleError {
+    MissingField,
+    Fen(FenError),
+    /// A move of the line couldn't be read or isn't legal.
+    IllegalMove(String),
+    /// The line doesn't have a move for the player.
+    EmptySolution,
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzzleError::MissingField => write!(f, "missing field"),
+            PuzzleError::Fen(error) => write!(f, "invalid fen: {}", error),
+            PuzzleError::IllegalMove(text) => write!(f, "illegal move: {}", text),
+            PuzzleError::EmptySolution => write!(f, "empty solution"),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+/// What happened after the player tried a move.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Attempt {
+    /// The move was right and the opponent replied with `reply`.
+    Correct { reply: Move },
+    /// The move was right and the puzzle is over.
+    Solved,
+    /// The move wasn't the solution and wasn't played.
+    Incorrect,
+}
+
+pub struct Puzzle {
+    pub id: String,
+    pub rating: Option<u32>,
+    pub themes: Vec<String>,
+    /// The player's moves and the opponent's replies, starting with the player's move.
+    pub solution: Vec<Move>,
+    game: Game,
+    start: Game,
+    /// Index in `solution` of the next move.
+    next: usize,
+}
+
+/// Reads a line of moves like `e2e4 e7e5` or `b2b1q`, checking that each can be played.
+/// A promotion without a piece is to a queen.
+fn parse_line(game: &Game, text: &str) -> Result<Vec<Move>, PuzzleError> {
+    let mut game = game.clone();
+    let mut line = vec![];
+    for word in text.split_whitespace() {
+        let m = Move::parse(word).ok_or_else(|| PuzzleError::IllegalMove(word.to_string()))?;
+        game.play_move(&m).ok_or_else(|| PuzzleError::IllegalMove(word.to_string()))?;
+        line.push(game.history.last().cloned().unwrap_or(m));
+    }
+    Ok(line)
+}
+
+impl Puzzle {
+    /// Creates a puzzle from a position and its solution, with moves written like `e2e4` or `e7e8n`.
+    pub fn new(fen: &str, solution: &str) -> Result<Puzzle, PuzzleError> {
+        let game = Game::from_fen(fen).map_err(PuzzleError::Fen)?;
+        let solution = parse_line(&game, solution)?;
+        if solution.is_empty() {
+            return Err(PuzzleError::EmptySolution);
+        }
+        Ok(Puzzle {
+            id: String::new(),
+            rating: None,
+            themes: vec![],
+            solution,
+            start: game.clone(),
+            game,
+            next: 0,
+        })
+    }
+
+    /// Reads a puzzle from a line of the lichess puzzle database, whose columns are
+    /// `PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,...`.
+    /// Lichess gives the position before the opponent's last move, so that move is played first.
+    pub fn from_lichess_csv(line: &str) -> Result<Puzzle, PuzzleError> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() < 3 {
+            return Err(PuzzleError::MissingField);
+        }
+        let mut game = Game::from_fen(fields[1]).map_err(PuzzleError::Fen)?;
+        let mut moves = parse_line(&game, fields[2])?;
+        if moves.len() < 2 {
+            return Err(PuzzleError::EmptySolution);
+        }
+        let setup = moves.remove(0);
+        game.play_move(&setup);
+
+        Ok(Puzzle {
+            id: fields[0].to_string(),
+            rating: fields.get(3).and_then(|rating| rating.parse().ok()),
+            themes: fields.get(7).map_or(vec![], |themes| themes.split_whitespace().map(str::to_string).collect()),
+            solution: moves,
+            start: game.clone(),
+            game,
+            next: 0,
+        })
+    }
+
+    /// The position the player has to move in.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.next >= self.solution.len()
+    }
+
+    /// The move the player should make next, if the puzzle isn't solved.
+    pub fn hint(&self) -> Option<&Move> {
+        self.solution.get(self.next)
+    }
+
+    /// Plays the player's move if it is the solution, and the opponent's reply after it.
+    pub fn try_move(&mut self, m: &Move) -> Attempt {
+        if self.is_solved() {
+            return Attempt::Incorrect;
+        }
+
+        let mut after = self.game.clone();
+        if after.play_move(m).is_none() {
+            return Attempt::Incorrect;
+        }
+        // As played, with the promotion piece filled in
+        let played = after.history.last().cloned().unwrap_or_else(|| m.clone());
+        if after.is_checkmate() {
+            self.game = after;
+            self.next = self.solution.len();
+            return Attempt::Solved;
+        }
+        if played != self.solution[self.next] {
+            return Attempt::Incorrect;
+        }
+
+        self.game = after;
+        self.next += 1;
+        match self.solution.get(self.next).cloned() {
+            Some(reply) => {
+                self.game.play_move(&reply);
+                self.next += 1;
+                if self.is_solved() {
+                    // The line ends with the opponent's move
+                    return Attempt::Solved;
+                }
+                Attempt::Correct { reply }
+            },
+            None => Attempt::Solved,
+        }
+    }
+
+    /// Goes back to the starting position.
+    pub fn reset(&mut self) {
+        self.game = self.start.clone();
+        self.next = 0;
+    }
+}
+
+/// Reads every puzzle in a lichess puzzle CSV, skipping the header and lines that can't be read.
+pub fn read_lichess_csv(text: &str) -> Vec<Puzzle> {
+    text.lines()
+        .filter(|line| !line.starts_with("PuzzleId"))
+        .filter_map(|line| Puzzle::from_lichess_csv(line).ok())
+        .collect()
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{read_lichess_csv, Attempt, Puzzle, PuzzleError};
+    use crate::Move;
+
+    fn m(text: &str) -> Move {
+        Move::parse(text).unwrap()
+    }
+
+    #[test]
+    fn solve_line() {
+        // Rxe8+ Qxe8 Rxe8#
+        let mut puzzle = Puzzle::new("3qr1k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1", "e2e8 d8e8 e1e8").unwrap();
+        assert_eq!(puzzle.try_move(&m("e2e7")), Attempt::Incorrect);
+        assert_eq!(puzzle.try_move(&m("e2e8")), Attempt::Correct { reply: m("d8e8") });
+        assert_eq!(puzzle.hint(), Some(&m("e1e8")));
+        assert_eq!(puzzle.try_move(&m("e1e8")), Attempt::Solved);
+        assert!(puzzle.is_solved());
+
+        puzzle.reset();
+        assert!(!puzzle.is_solved());
+        assert_eq!(puzzle.hint(), Some(&m("e2e8")));
+    }
+
+    #[test]
+    fn alternative_mate_is_accepted() {
+        // Both rooks mate on the back rank, the line only has one
+        let mut puzzle = Puzzle::new("6k1/5ppp/8/8/8/8/8/R3R1K1 w - - 0 1", "a1a8").unwrap();
+        assert_eq!(puzzle.try_move(&m("e1e8")), Attempt::Solved);
+    }
+
+    #[test]
+    fn lichess_csv() {
+        // The second puzzle's line has an illegal move
+        let text = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags\n\
+                    00001,6k1/5ppp/8/8/8/8/r5PP/R5K1 b - - 0 1,a2b2 a1a8,600,75,90,100,backRankMate mateIn1,https://lichess.org/abc,\n\
+                    00002,6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1,a1a2 g8g6,600,75,90,100,short,,\n";
+        let mut puzzles = read_lichess_csv(text);
+        assert_eq!(puzzles.len(), 1);
+
+        let puzzle = &mut puzzles[0];
+        assert_eq!(puzzle.id, "00001");
+        assert_eq!(puzzle.rating, Some(600));
+        assert_eq!(puzzle.themes, vec!["backRankMate", "mateIn1"]);
+        // The opponent's a2b2 has been played already
+        assert_eq!(puzzle.solution, vec![m("a1a8")]);
+        assert_eq!(puzzle.try_move(&m("a1a8")), Attempt::Solved);
+
+        assert_eq!(Puzzle::from_lichess_csv("00003,8/8/8/8/8/8/8/8 w - - 0 1").err(), Some(PuzzleError::MissingField));
+    }
+
+    #[test]
+    fn promotion_in_solution() {
+        let mut puzzle = Puzzle::from_lichess_csv("00004,8/8/8/8/8/8/1p3k2/7K w - - 0 1,h1h2 b2b1n,1500,75,90,100,promotion,,").unwrap();
+        assert_eq!(puzzle.solution, vec![m("b2b1n")]);
+        // Without a piece the pawn becomes a queen
+        assert_eq!(puzzle.try_move(&m("b2b1")), Attempt::Incorrect);
+        assert_eq!(puzzle.try_move(&m("b2b1n")), Attempt::Solved);
+        assert_eq!(puzzle.game().to_fen(), "8/8/8/8/8/8/5k1K/1n6 w - - 0 2");
+
+        let puzzle = Puzzle::new("4k3/8/8/8/8/8/1p6/4K3 b - - 0 1", "b2b1").unwrap();
+        assert_eq!(puzzle.solution, vec![m("b2b1q")]);
+    }
+}
@@ -0,0 +1,534 @@
+//! Drawing positions as PNG images and games as animated GIFs.
+//!
+//! Pieces are drawn from the small bitmaps below, scaled up. Both encoders
+//! are written here so no image crate is needed; PNG data is stored without
+//! compression, GIF data is LZW compressed as the format requires.
+
+use std::collections::HashMap;
+
+use crate::{Colour, Game, PieceType};
+
+const SPRITE_SIZE: usize = 16;
+
+// '#' is the outline, 'o' the body of the piece
+const PAWN: [&str; SPRITE_SIZE] = [
+    "                ",
+    "                ",
+    "      ####      ",
+    "     #oooo#     ",
+    "     #oooo#     ",
+    "      #oo#      ",
+    "     #oooo#     ",
+    "      #oo#      ",
+    "      #oo#      ",
+    "     #oooo#     ",
+    "    #oooooo#    ",
+    "   #oooooooo#   ",
+    "   ##########   ",
+    "                ",
+    "                ",
+    "                ",
+];
+const KNIGHT: [&str; SPRITE_SIZE] = [
+    "                ",
+    "       # #      ",
+    "      #o#o#     ",
+    "     #ooooo#    ",
+    "    #oo#oooo#   ",
+    "   #oooooooo#   ",
+    "  #ooooooooo#   ",
+    "  #oo##ooooo#   ",
+    "   ##  #oooo#   ",
+    "      #ooooo#   ",
+    "     #oooooo#   ",
+    "    #ooooooo#   ",
+    "   #oooooooo#   ",
+    "   ##########   ",
+    "                ",
+    "                ",
+];
+const BISHOP: [&str; SPRITE_SIZE] = [
+    "                ",
+    "       ##       ",
+    "      #oo#      ",
+    "     #oo#o#     ",
+    "    #oo#ooo#    ",
+    "    #o#oooo#    ",
+    "    #oooooo#    ",
+    "     #oooo#     ",
+    "      #oo#      ",
+    "     #oooo#     ",
+    "     #oooo#     ",
+    "    #oooooo#    ",
+    "  #oooooooooo#  ",
+    "  ############  ",
+    "                ",
+    "                ",
+];
+const ROOK: [&str; SPRITE_SIZE] = [
+    "                ",
+    "   ## #### ##   ",
+    "   #o##oo##o#   ",
+    "   #oooooooo#   ",
+    "    #oooooo#    ",
+    "    #oooooo#    ",
+    "    #oooooo#    ",
+    "    #oooooo#    ",
+    "    #oooooo#    ",
+    "    #oooooo#    ",
+    "   #oooooooo#   ",
+    "  #oooooooooo#  ",
+    "  ############  ",
+    "                ",
+    "                ",
+    "                ",
+];
+const QUEEN: [&str; SPRITE_SIZE] = [
+    "                ",
+    " #     ##     # ",
+    " #o#  #oo#  #o# ",
+    "  #o##oooo##o#  ",
+    "  #oooooooooo#  ",
+    "   #oooooooo#   ",
+    "   #oooooooo#   ",
+    "    #oooooo#    ",
+    "    #oooooo#    ",
+    "    #oooooo#    ",
+    "   #oooooooo#   ",
+    "  #oooooooooo#  ",
+    "  ############  ",
+    "                ",
+    "                ",
+    "                ",
+];
+const KING: [&str; SPRITE_SIZE] = [
+    "       ##       ",
+    "      #oo#      ",
+    "    ##oooo##    ",
+    "      #oo#      ",
+    "  ####oooo####  ",
+    " #oooooooooooo# ",
+    " #oooooooooooo# ",
+    "  #oooooooooo#  ",
+    "   #oooooooo#   ",
+    "    #oooooo#    ",
+    "    #oooooo#    ",
+    "   #oooooooo#   ",
+    "  #oooooooooo#  ",
+    "  ############  ",
+    "                ",
+    "                ",
+];
+
+fn sprite(piece_type: PieceType) -> &'static [&'static str; SPRITE_SIZE] {
+    match piece_type {
+        PieceType::PAWN => &PAWN,
+        PieceType::KNIGHT => &KNIGHT,
+        PieceType::BISHOP => &BISHOP,
+        PieceType::ROOK => &ROOK,
+        PieceType::QUEEN => &QUEEN,
+        PieceType::KING => &KING,
+    }
+}
+
+// Indices into the palette
+const LIGHT: u8 = 0;
+const DARK: u8 = 1;
+const LAST_MOVE: u8 = 2;
+const CHECK: u8 = 3;
+const WHITE_PIECE: u8 = 4;
+const BLACK_PIECE: u8 = 5;
+const BLACK_OUTLINE: u8 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RasterOptions {
+    /// Size of a sprite pixel, a square is 16 times as wide. At least 1, and
+    /// at most `MAX_GIF_SCALE` for a GIF.
+    pub scale: usize,
+    pub light: [u8; 3],
+    pub dark: [u8; 3],
+    /// Shows the board from black's side.
+    pub flipped: bool,
+    /// Highlights the squares of the last move in `history`.
+    pub last_move: bool,
+    pub last_move_colour: [u8; 3],
+    /// Highlights the king of the player to move if it is in check.
+    pub check: bool,
+    pub check_colour: [u8; 3],
+    /// Time each position is shown in a GIF, in hundredths of a second.
+    pub frame_delay: u16,
+}
+
+impl Default for RasterOptions {
+    fn default() -> RasterOptions {
+        RasterOptions {
+            scale: 3,
+            light: [0xf0, 0xd9, 0xb5],
+            dark: [0xb5, 0x88, 0x63],
+            flipped: false,
+            last_move: true,
+            last_move_colour: [0xcd, 0xd2, 0x6a],
+            check: true,
+            check_colour: [0xe0, 0x50, 0x50],
+            frame_delay: 100,
+        }
+    }
+}
+
+impl RasterOptions {
+    /// The largest scale whose board fits in the 16 bit sizes of a GIF.
+    pub const MAX_GIF_SCALE: usize = u16::MAX as usize / (SPRITE_SIZE * 8);
+
+    fn palette(&self) -> Vec<[u8; 3]> {
+        vec![self.light, self.dark, self.last_move_colour, self.check_colour, [0xff; 3], [0x00; 3], [0xa0; 3], [0x00; 3]]
+    }
+}
+
+/// An image as indices into the palette.
+struct Frame {
+    size: usize,
+    pixels: Vec<u8>,
+}
+
+impl Game {
+    fn draw(&self, options: &RasterOptions) -> Frame {
+        let square_size = SPRITE_SIZE * options.scale;
+        let size = square_size * 8;
+        let mut pixels = vec![0; size * size];
+
+        let last_move = self.history.last().filter(|_| options.last_move);
+        let checked_king = match options.check && self.is_in_check(self.active_colour) {
+            true => crate::attacks::find_king(&self.board, self.active_colour),
+            false => None,
+        };
+        for y in 0..8 {
+            for x in 0..8 {
+                let square = vec![y, x];
+                let mut background = if (y + x) % 2 == 0 { LIGHT } else { DARK };
+                if last_move.is_some_and(|m| m.from == square || m.to == square) {
+                    background = LAST_MOVE;
+                }
+                if checked_king.as_ref() == Some(&square) {
+                    background = CHECK;
+                }
+                let (row, column) = if options.flipped { (7 - y, 7 - x) } else { (y, x) };
+                let piece = self.board[y][x];
+                for py in 0..square_size {
+                    for px in 0..square_size {
+                        let mut colour = background;
+                        if let Some(piece) = piece {
+                            let sprite_row = sprite(piece.piece_type)[py / options.scale].as_bytes();
+                            colour = match (sprite_row[px / options.scale], piece.color) {
+                                (b'#', Colour::White) => BLACK_PIECE,
+                                (b'#', Colour::Black) => BLACK_OUTLINE,
+                                (b'o', Colour::White) => WHITE_PIECE,
+                                (b'o', Colour::Black) => BLACK_PIECE,
+                                _ => background,
+                            };
+                        }
+                        pixels[(row * square_size + py) * size + column * square_size + px] = colour;
+                    }
+                }
+            }
+        }
+        Frame { size, pixels }
+    }
+
+    /// Draws the position as a PNG image, `None` if the scale is 0.
+    pub fn to_png(&self, options: &RasterOptions) -> Option<Vec<u8>> {
+        if options.scale == 0 {
+            return None;
+        }
+        Some(encode_png(&self.draw(options), &options.palette()))
+    }
+
+    /// Draws every position of the game, from `start_fen` to now, as an animated GIF.
+    /// `None` if the scale is 0 or above `RasterOptions::MAX_GIF_SCALE`.
+    pub fn to_gif(&self, options: &RasterOptions) -> Option<Vec<u8>> {
+        if !(1..=RasterOptions::MAX_GIF_SCALE).contains(&options.scale) {
+            return None;
+        }
+        let mut frames = vec![];
+        if let Ok(mut replay) = Game::from_fen(&self.start_fen) {
+            frames.push(replay.draw(options));
+            for m in &self.history {
+                if replay.play_move(m).is_none() {
+                    break;
+                }
+                frames.push(replay.draw(options));
+            }
+        }
+        else {
+            frames.push(self.draw(options));
+        }
+        Some(encode_gif(&frames, &options.palette(), options.frame_delay))
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+fn encode_png(frame: &Frame, palette: &[[u8; 3]]) -> Vec<u8> {
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+    let mut header = vec![];
+    header.extend((frame.size as u32).to_be_bytes());
+    header.extend((frame.size as u32).to_be_bytes());
+    // 8 bits per pixel, indexed colour, default compression, filter and interlacing
+    header.extend([8, 3, 0, 0, 0]);
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"PLTE", &palette.concat());
+
+    // Every row starts with filter type 0
+    let mut raw = vec![];
+    for row in frame.pixels.chunks(frame.size) {
+        raw.push(0);
+        raw.extend(row);
+    }
+    // A zlib stream of uncompressed deflate blocks
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(65535).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push(if i == blocks.len() - 1 { 1 } else { 0 });
+        zlib.extend((block.len() as u16).to_le_bytes());
+        zlib.extend((!(block.len() as u16)).to_le_bytes());
+        zlib.extend(*block);
+    }
+    zlib.extend(adler32(&raw).to_be_bytes());
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Writes codes of varying width, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// LZW compression as used by GIF.
+fn lzw_encode(pixels: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = min_code_size + 1;
+    writer.write(clear, width);
+
+    let Some((first, rest)) = pixels.split_first() else {
+        writer.write(end, width);
+        return writer.finish();
+    };
+    let mut prefix = *first as u16;
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        writer.write(prefix, width);
+        if next < 4096 {
+            table.insert((prefix, pixel), next);
+            // The decoder widens its codes once the table needs more bits
+            if next == 1 << width && width < 12 {
+                width += 1;
+            }
+            next += 1;
+        }
+        else {
+            writer.write(clear, width);
+            table.clear();
+            next = end + 1;
+            width = min_code_size + 1;
+        }
+        prefix = pixel as u16;
+    }
+    writer.write(prefix, width);
+    writer.write(end, width);
+    writer.finish()
+}
+
+fn encode_gif(frames: &[Frame], palette: &[[u8; 3]], delay: u16) -> Vec<u8> {
+    let size = frames.first().map_or(0, |frame| frame.size) as u16;
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend(size.to_le_bytes());
+    gif.extend(size.to_le_bytes());
+    // Global colour table of 8 colours
+    gif.extend([0b1111_0010, 0, 0]);
+    gif.extend(palette.concat());
+    // Loop forever
+    gif.extend([0x21, 0xff, 0x0b]);
+    gif.extend(b"NETSCAPE2.0");
+    gif.extend([0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    for frame in frames {
+        gif.extend([0x21, 0xf9, 0x04, 0x00]);
+        gif.extend(delay.to_le_bytes());
+        gif.extend([0x00, 0x00]);
+
+        gif.push(0x2c);
+        gif.extend([0, 0, 0, 0]);
+        gif.extend(size.to_le_bytes());
+        gif.extend(size.to_le_bytes());
+        gif.push(0);
+
+        let min_code_size = 3;
+        gif.push(min_code_size as u8);
+        for block in lzw_encode(&frame.pixels, min_code_size).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend(block);
+        }
+        gif.push(0);
+    }
+    gif.push(0x3b);
+    gif
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, lzw_encode, RasterOptions, SPRITE_SIZE};
+    use crate::Game;
+
+    /// GIF's LZW decompression, to check the encoder against.
+    fn lzw_decode(data: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let mut table: Vec<Vec<u8>> = vec![];
+        let mut width = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = vec![];
+        let (mut buffer, mut bits, mut bytes) = (0u32, 0u32, data.iter());
+        loop {
+            while bits < width {
+                buffer |= (*bytes.next().unwrap() as u32) << bits;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << width) - 1)) as usize;
+            buffer >>= width;
+            bits -= width;
+
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).chain([vec![], vec![]]).collect();
+                width = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return output;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.clone(), vec![previous[0]]].concat(),
+                (None, None) => panic!("bad code"),
+            };
+            if let Some(previous) = previous {
+                table.push([previous, vec![entry[0]]].concat());
+                if table.len() == 1 << width && width < 12 {
+                    width += 1;
+                }
+            }
+            output.extend(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let pixels: Vec<u8> = (0..20000u32).map(|i| ((i / 7 + i * i / 13) % 8) as u8).collect();
+        assert_eq!(lzw_decode(&lzw_encode(&pixels, 3), 3), pixels);
+        let flat = vec![1; 50000];
+        assert_eq!(lzw_decode(&lzw_encode(&flat, 3), 3), flat);
+    }
+
+    #[test]
+    fn png_chunks() {
+        let png = Game::new().to_png(&RasterOptions { scale: 1, ..RasterOptions::default() }).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        // 128 by 128
+        assert_eq!(&png[16..24], &[0, 0, 0, 128, 0, 0, 0, 128]);
+        assert_eq!(u32::from_be_bytes(png[29..33].try_into().unwrap()), crc32(&png[12..29]));
+        assert_eq!(&png[png.len() - 12..], b"\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+    }
+
+    #[test]
+    fn gif_has_a_frame_per_position() {
+        let mut game = Game::new();
+        game.make_move(vec![6, 4], vec![4, 4]);
+        game.make_move(vec![1, 4], vec![3, 4]);
+        let gif = game.to_gif(&RasterOptions::default()).unwrap();
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(gif.last(), Some(&0x3b));
+        // A graphic control extension for each frame
+        assert_eq!(gif.windows(3).filter(|bytes| bytes == &[0x21, 0xf9, 0x04]).count(), 3);
+    }
+
+    #[test]
+    fn scale_limits() {
+        let game = Game::new();
+        assert_eq!(game.to_png(&RasterOptions { scale: 0, ..RasterOptions::default() }), None);
+        assert_eq!(game.to_gif(&RasterOptions { scale: 0, ..RasterOptions::default() }), None);
+        // 512 would make the board 65536 pixels wide
+        assert_eq!(RasterOptions::MAX_GIF_SCALE, 511);
+        assert_eq!(game.to_gif(&RasterOptions { scale: 512, ..RasterOptions::default() }), None);
+        let gif = game.to_gif(&RasterOptions { scale: 1, ..RasterOptions::default() }).unwrap();
+        assert_eq!(&gif[6..10], &[128, 0, 128, 0]);
+    }
+
+    #[test]
+    fn sprites_are_square() {
+        for sprite in [super::PAWN, super::KNIGHT, super::BISHOP, super::ROOK, super::QUEEN, super::KING] {
+            assert!(sprite.iter().all(|row| row.len() == SPRITE_SIZE));
+        }
+    }
+}
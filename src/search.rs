@@ -0,0 +1,726 @@
+//! Engine search: alpha-beta to a fixed depth with a capture search at the leaves.
+//!
+//! The search deepens one half move at a time and remembers positions in a
+//! transposition table. With more than one thread it is a Lazy SMP search:
+//! every thread searches the whole tree and they share only the table.
+//!
+//! Positions are scored by the classical evaluation below unless another
+//! `Evaluator`, such as the network in `nnue`, is passed in. An `Engine` can
+//! also be given a `Tablebase` for positions with few pieces.
+//!
+//! Scores are in centipawns from the point of view of the player to move.
+//! Mate is scored as `MATE` minus the number of half moves to it, `Score`
+//! turns that into mate in a number of moves for showing to people.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::attacks::find_king;
+use crate::tablebase::{piece_count, root_moves, Tablebase};
+use crate::tt::{Bound, TranspositionTable, TtEntry};
+use crate::{Colour, Game, Move, Piece, PieceType};
+
+pub const MATE: i32 = 30_000;
+/// Scores further from zero than this are mates.
+pub const MATE_BOUND: i32 = MATE - 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchOptions {
+    /// Half moves to search.
+    pub depth: u32,
+    /// One thread gives the same result every time, more can differ from run to run.
+    pub threads: usize,
+    /// Size of the transposition table in megabytes.
+    pub hash_mb: usize,
+    /// Number of best moves to find lines for.
+    pub multi_pv: usize,
+    /// Stops deepening after this many nodes. The first half move is always searched.
+    pub max_nodes: Option<u64>,
+    /// Stops deepening once this much time has passed. The first half move is always searched.
+    pub max_time: Option<Duration>,
+    /// Only used by the classical evaluation.
+    pub style: Style,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions { depth: 4, threads: 1, hash_mb: 16, multi_pv: 1, max_nodes: None, max_time: None, style: Style::default() }
+    }
+}
+
+/// Extra weights in the evaluation that change how the engine plays. Both are
+/// centipawns per square a piece stands closer to a king, zero is neutral.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Style {
+    /// Weight for pieces near the opponent's king.
+    pub aggression: i32,
+    /// Weight for pieces near the own king.
+    pub defence: i32,
+}
+
+impl Style {
+    pub fn aggressive() -> Style {
+        Style { aggression: 8, defence: 0 }
+    }
+
+    pub fn defensive() -> Style {
+        Style { aggression: 0, defence: 8 }
+    }
+}
+
+/// A score as shown to people.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Moves until mate, negative if the player to move is getting mated.
+    Mate(i32),
+}
+
+impl Score {
+    pub fn from_search(score: i32) -> Score {
+        match score {
+            score if score > MATE_BOUND => Score::Mate((MATE - score + 1) / 2),
+            score if score < -MATE_BOUND => Score::Mate(-(MATE + score + 1) / 2),
+            score => Score::Centipawns(score),
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Score::Centipawns(centipawns) => write!(f, "{:+.2}", centipawns as f64 / 100.0),
+            Score::Mate(moves) if moves < 0 => write!(f, "mated in {}", -moves),
+            Score::Mate(moves) => write!(f, "mate in {}", moves),
+        }
+    }
+}
+
+/// One of the best moves and the line following it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PvLine {
+    pub score: Score,
+    pub pv: Vec<Move>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    /// Principal variation, the best line for both players.
+    pub pv: Vec<Move>,
+    /// The best `multi_pv` lines, best first. The first is `pv`.
+    pub lines: Vec<PvLine>,
+    /// Nodes searched by all threads.
+    pub nodes: u64,
+    /// Half moves searched by the last iteration that finished.
+    pub depth: u32,
+}
+
+/// Returns true if the score means one of the players is getting mated.
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() > MATE_BOUND
+}
+
+/// Bonus for a piece standing on [y, x], seen from white's side of the board.
+fn placement_bonus(piece_type: PieceType, colour: Colour, y: usize, x: usize) -> i32 {
+    // 0 on the edge, 3 in the middle
+    let centre = 3 - (2 * x as i32 - 7).abs().max((2 * y as i32 - 7).abs()) / 2;
+    let advanced = match colour {
+        Colour::White => 6 - y as i32,
+        Colour::Black => y as i32 - 1,
+    };
+    match piece_type {
+        PieceType::PAWN => advanced * 5 + if (3..=4).contains(&x) { centre * 5 } else { 0 },
+        PieceType::KNIGHT => centre * 10,
+        PieceType::BISHOP | PieceType::QUEEN => centre * 4,
+        PieceType::ROOK | PieceType::KING => 0,
+    }
+}
+
+/// Material and piece placement, from the point of view of the player to move.
+pub fn evaluate(game: &Game) -> i32 {
+    evaluate_with(game, &Style::default())
+}
+
+/// `evaluate` with the weights of a playing style added.
+pub fn evaluate_with(game: &Game, style: &Style) -> i32 {
+    let kings = match *style == Style::default() {
+        true => None,
+        false => find_king(&game.board, Colour::White).zip(find_king(&game.board, Colour::Black)),
+    };
+    let closeness = |square: &[usize], y: usize, x: usize| 7 - square[0].abs_diff(y).max(square[1].abs_diff(x)) as i32;
+
+    let mut score = 0;
+    for (y, row) in game.board.iter().enumerate() {
+        for (x, square) in row.iter().enumerate() {
+            if let Some(piece) = square {
+                let mut value = piece.piece_type.value() + placement_bonus(piece.piece_type, piece.color, y, x);
+                if let Some((white_king, black_king)) = kings.as_ref().filter(|_| piece.piece_type != PieceType::KING) {
+                    let (own, opponent) = match piece.color {
+                        Colour::White => (white_king, black_king),
+                        Colour::Black => (black_king, white_king),
+                    };
+                    value += style.aggression * closeness(opponent, y, x) + style.defence * closeness(own, y, x);
+                }
+                score += if piece.color == game.active_colour { value } else { -value };
+            }
+        }
+    }
+    score
+}
+
+/// Scores positions for the search, from the point of view of the player to move.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, game: &Game) -> i32;
+
+    /// An evaluation for one search thread that is told about every move
+    /// the search makes and takes back. By default every position is
+    /// evaluated from scratch.
+    fn incremental(&self, _root: &Game) -> Box<dyn IncrementalEvaluator + '_> {
+        Box::new(FromScratch(self))
+    }
+}
+
+pub trait IncrementalEvaluator {
+    /// `m` is played in `game`.
+    fn make_move(&mut self, game: &Game, m: &Move);
+    /// The last move made is taken back.
+    fn undo_move(&mut self);
+    /// Scores `game`, the position after the moves made so far.
+    fn evaluate(&mut self, game: &Game) -> i32;
+}
+
+struct FromScratch<'a, E: ?Sized>(&'a E);
+
+impl<E: Evaluator + ?Sized> IncrementalEvaluator for FromScratch<'_, E> {
+    fn make_move(&mut self, _game: &Game, _m: &Move) {}
+
+    fn undo_move(&mut self) {}
+
+    fn evaluate(&mut self, game: &Game) -> i32 {
+        self.0.evaluate(game)
+    }
+}
+
+/// Material, piece placement and the weights of a style.
+pub struct Classical {
+    pub style: Style,
+}
+
+impl Evaluator for Classical {
+    fn evaluate(&self, game: &Game) -> i32 {
+        evaluate_with(game, &self.style)
+    }
+}
+
+/// The position after `m`, which must be legal. Unlike `make_move` nothing but
+/// the board and the player to move is updated.
+pub(crate) fn play(game: &Game, m: &Move) -> Game {
+    let mut child = game.clone();
+    let mut piece = child.board[m.from[0]][m.from[1]];
+    if let Some(pawn) = piece.filter(|_| game.is_promotion(&m.from, &m.to)) {
+        piece = Some(Piece { color: pawn.color, piece_type: m.promotion.unwrap_or(game.promotion_type) });
+    }
+    child.board[m.to[0]][m.to[1]] = piece;
+    child.board[m.from[0]][m.from[1]] = None;
+    child.active_colour = game.active_colour.opposite();
+    child
+}
+
+/// Legal moves with captures first, most valuable victim and least valuable attacker first.
+pub(crate) fn ordered_moves(game: &Game) -> Vec<Move> {
+    let mut moves = game.legal_moves();
+    let order = |m: &Move| match game.board[m.to[0]][m.to[1]] {
+        Some(victim) => victim.piece_type.value() * 10 - game.board[m.from[0]][m.from[1]].unwrap().piece_type.value() / 10,
+        None => 0,
+    };
+    moves.sort_by_key(|m| -order(m));
+    moves
+}
+
+// Mate scores are stored as distance from the stored position rather than from the root
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    match score {
+        score if score > MATE_BOUND => score + ply,
+        score if score < -MATE_BOUND => score - ply,
+        score => score,
+    }
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    match score {
+        score if score > MATE_BOUND => score - ply,
+        score if score < -MATE_BOUND => score + ply,
+        score => score,
+    }
+}
+
+struct Searcher<'a> {
+    table: &'a TranspositionTable,
+    /// Helper threads are stopped when the main thread is done.
+    stop: Option<&'a AtomicBool>,
+    /// Root moves left out, because lines for them were already found.
+    excluded: Vec<Move>,
+    /// The only root moves searched, if the tablebase chose them.
+    root_moves: Option<&'a [Move]>,
+    evaluator: Box<dyn IncrementalEvaluator + 'a>,
+    tablebase: Option<&'a dyn Tablebase>,
+    nodes: u64,
+    node_limit: u64,
+    deadline: Option<Instant>,
+    timed_out: bool,
+}
+
+impl Searcher<'_> {
+    fn stopped(&self) -> bool {
+        self.nodes > self.node_limit || self.timed_out || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    fn negamax(&mut self, game: &Game, depth: u32, ply: i32, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        self.nodes += 1;
+        // The clock is only read now and then
+        if self.nodes.is_multiple_of(1024) && self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            self.timed_out = true;
+        }
+        if self.stopped() {
+            return 0;
+        }
+        if ply > 0 {
+            if let Some(wdl) = self.tablebase
+                .filter(|tablebase| piece_count(game) <= tablebase.max_pieces())
+                .and_then(|tablebase| tablebase.probe_wdl(game))
+            {
+                pv.clear();
+                return wdl.score();
+            }
+        }
+        if depth == 0 {
+            pv.clear();
+            return self.quiescence(game, alpha, beta);
+        }
+
+        let key = game.zobrist_hash();
+        let entry = self.table.probe(key);
+        // Not at the root, where the move is needed
+        if let Some(entry) = entry.as_ref().filter(|entry| ply > 0 && entry.depth >= depth) {
+            let score = score_from_table(entry.score, ply);
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if usable {
+                pv.clear();
+                return score;
+            }
+        }
+
+        let mut moves = ordered_moves(game);
+        if moves.is_empty() {
+            pv.clear();
+            return if game.is_in_check(game.active_colour) { -MATE + ply } else { 0 };
+        }
+        if let Some(root_moves) = self.root_moves.filter(|_| ply == 0) {
+            moves.retain(|m| root_moves.contains(m));
+        }
+        if ply == 0 && !self.excluded.is_empty() {
+            moves.retain(|m| !self.excluded.contains(m));
+            if moves.is_empty() {
+                pv.clear();
+                return -MATE - 1;
+            }
+        }
+        // The best move found before is tried first
+        if let Some(best) = entry.and_then(|entry| entry.best_move) {
+            if let Some(i) = moves.iter().position(|m| *m == best) {
+                let m = moves.remove(i);
+                moves.insert(0, m);
+            }
+        }
+
+        let mut best_move = None;
+        let mut line = vec![];
+        for m in moves {
+            self.evaluator.make_move(game, &m);
+            let score = -self.negamax(&play(game, &m), depth - 1, ply + 1, -beta, -alpha, &mut line);
+            self.evaluator.undo_move();
+            if self.stopped() {
+                return 0;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(m.clone());
+                pv.clear();
+                pv.push(m);
+                pv.append(&mut line);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        let bound = match best_move {
+            _ if alpha >= beta => Bound::Lower,
+            Some(_) => Bound::Exact,
+            None => Bound::Upper,
+        };
+        // Without all its moves the root's score isn't its own
+        if ply > 0 || self.excluded.is_empty() {
+            self.table.store(key, &TtEntry { best_move, score: score_to_table(alpha, ply), depth, bound });
+        }
+        alpha
+    }
+
+    /// Only captures are searched, and the player to move may stop capturing.
+    fn quiescence(&mut self, game: &Game, mut alpha: i32, beta: i32) -> i32 {
+        let stand_pat = self.evaluator.evaluate(game);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        for m in ordered_moves(game) {
+            if game.board[m.to[0]][m.to[1]].is_none() {
+                break;
+            }
+            self.nodes += 1;
+            self.evaluator.make_move(game, &m);
+            let score = -self.quiescence(&play(game, &m), -beta, -alpha);
+            self.evaluator.undo_move();
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        alpha
+    }
+}
+
+/// Lengthens a principal variation cut short by the table with the moves stored in it.
+fn extend_pv(table: &TranspositionTable, root: &Game, pv: &mut Vec<Move>, depth: u32) {
+    let mut position = pv.iter().fold(root.clone(), |position, m| play(&position, m));
+    while pv.len() < depth as usize {
+        let Some(m) = table.probe(position.zobrist_hash()).and_then(|entry| entry.best_move) else { break };
+        if !position.legal_moves().contains(&m) {
+            break;
+        }
+        position = play(&position, &m);
+        pv.push(m);
+    }
+}
+
+/// Searches the position `depth` half moves deep on one thread.
+pub fn search(game: &Game, depth: u32) -> SearchResult {
+    search_with(game, &SearchOptions { depth, ..SearchOptions::default() })
+}
+
+/// Searches the position with the given options and a table of its own.
+pub fn search_with(game: &Game, options: &SearchOptions) -> SearchResult {
+    search_in(game, options, &TranspositionTable::with_size_mb(options.hash_mb), &Classical { style: options.style }, None)
+}
+
+/// `search_with` scoring positions with `evaluator` instead of the classical evaluation.
+pub fn search_with_evaluator(game: &Game, options: &SearchOptions, evaluator: &dyn Evaluator) -> SearchResult {
+    search_in(game, options, &TranspositionTable::with_size_mb(options.hash_mb), evaluator, None)
+}
+
+fn search_in(game: &Game, options: &SearchOptions, table: &TranspositionTable, evaluator: &dyn Evaluator, tablebase: Option<&dyn Tablebase>) -> SearchResult {
+    // The history isn't needed and would be copied in every node
+    let mut root = game.clone();
+    root.history.clear();
+    root.undo_stack.clear();
+    let root_moves = tablebase.and_then(|tablebase| root_moves(tablebase, &root));
+    let start = Instant::now();
+
+    let depth = options.depth.max(1);
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        // Half of the helpers search one half move deeper, so the threads don't all follow each other
+        let helpers: Vec<_> = (1..options.threads.max(1))
+            .map(|i| {
+                let root = root.clone();
+                let stop = &stop;
+                let root_moves = root_moves.as_deref();
+                scope.spawn(move || {
+                    let mut searcher = Searcher {
+                        table,
+                        stop: Some(stop),
+                        excluded: vec![],
+                        root_moves,
+                        evaluator: evaluator.incremental(&root),
+                        tablebase,
+                        nodes: 0,
+                        node_limit: u64::MAX,
+                        deadline: None,
+                        timed_out: false,
+                    };
+                    let mut pv = vec![];
+                    for helper_depth in 1 + (i % 2) as u32..=depth + 1 {
+                        searcher.negamax(&root, helper_depth, 0, -MATE - 1, MATE + 1, &mut pv);
+                        if searcher.stopped() {
+                            break;
+                        }
+                    }
+                    searcher.nodes
+                })
+            })
+            .collect();
+
+        let mut searcher = Searcher {
+            table,
+            stop: None,
+            excluded: vec![],
+            root_moves: root_moves.as_deref(),
+            evaluator: evaluator.incremental(&root),
+            tablebase,
+            nodes: 0,
+            node_limit: u64::MAX,
+            deadline: None,
+            timed_out: false,
+        };
+        let mut score = 0;
+        let mut lines = vec![];
+        let mut completed = 0;
+        for iteration_depth in 1..=depth {
+            if iteration_depth > 1 {
+                searcher.node_limit = options.max_nodes.unwrap_or(u64::MAX);
+                searcher.deadline = options.max_time.map(|time| start + time);
+            }
+            // Each line is searched without the first moves of the lines before it
+            searcher.excluded.clear();
+            let mut iteration_lines = vec![];
+            let mut iteration_score = 0;
+            for index in 0..options.multi_pv.max(1) {
+                let mut pv = vec![];
+                let line_score = searcher.negamax(&root, iteration_depth, 0, -MATE - 1, MATE + 1, &mut pv);
+                if index == 0 {
+                    iteration_score = line_score;
+                }
+                let Some(first) = pv.first() else { break };
+                searcher.excluded.push(first.clone());
+                iteration_lines.push((line_score, pv));
+            }
+            // An unfinished iteration is thrown away
+            if searcher.stopped() {
+                break;
+            }
+            score = iteration_score;
+            lines = iteration_lines;
+            completed = iteration_depth;
+        }
+        stop.store(true, Ordering::Relaxed);
+        let nodes = searcher.nodes + helpers.into_iter().map(|helper| helper.join().unwrap_or(0)).sum::<u64>();
+
+        lines.sort_by_key(|(line_score, _)| -line_score);
+        let lines: Vec<PvLine> = lines.into_iter()
+            .map(|(line_score, mut pv)| {
+                extend_pv(table, &root, &mut pv, completed);
+                PvLine { score: Score::from_search(line_score), pv }
+            })
+            .collect();
+        let pv = lines.first().map_or(vec![], |line| line.pv.clone());
+        SearchResult {
+            best_move: pv.first().cloned(),
+            score,
+            pv,
+            lines,
+            nodes,
+            depth: completed,
+        }
+    })
+}
+
+/// An engine keeps its transposition table from one search to the next, so
+/// what it learned while thinking about one move helps with the next.
+pub struct Engine {
+    pub options: SearchOptions,
+    table: TranspositionTable,
+    evaluator: Option<Box<dyn Evaluator>>,
+    tablebase: Option<Box<dyn Tablebase>>,
+}
+
+impl Engine {
+    pub fn new(options: SearchOptions) -> Engine {
+        let table = TranspositionTable::with_size_mb(options.hash_mb);
+        Engine { options, table, evaluator: None, tablebase: None }
+    }
+
+    /// Scores positions with `evaluator`, or with the classical evaluation if
+    /// it is `None`. The table is emptied since its scores came from the old one.
+    pub fn set_evaluator(&mut self, evaluator: Option<Box<dyn Evaluator>>) {
+        self.evaluator = evaluator;
+        self.table.clear();
+    }
+
+    /// Looks up positions with few pieces in `tablebase`. The table is
+    /// emptied since its scores were searched without it.
+    pub fn set_tablebase(&mut self, tablebase: Option<Box<dyn Tablebase>>) {
+        self.tablebase = tablebase;
+        self.table.clear();
+    }
+
+    /// Resizes the transposition table, which empties it.
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.options.hash_mb = megabytes;
+        self.table = TranspositionTable::with_size_mb(megabytes);
+    }
+
+    /// Forgets everything about earlier searches, for a new game.
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    pub fn search(&self, game: &Game) -> SearchResult {
+        self.table.new_search();
+        let tablebase = self.tablebase.as_deref();
+        match &self.evaluator {
+            Some(evaluator) => search_in(game, &self.options, &self.table, evaluator.as_ref(), tablebase),
+            None => search_in(game, &self.options, &self.table, &Classical { style: self.options.style }, tablebase),
+        }
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Engine {
+        Engine::new(SearchOptions::default())
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{evaluate, evaluate_with, is_mate_score, search, search_with, Engine, Score, SearchOptions, Style, MATE};
+    use crate::{Game, Move};
+
+    #[test]
+    fn start_position_is_equal() {
+        assert_eq!(evaluate(&Game::new()), 0);
+    }
+
+    #[test]
+    fn takes_hanging_queen() {
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = search(&game, 2);
+        assert_eq!(result.best_move, Move::parse("d2d5"));
+        // A rook up after taking
+        assert!(result.score > 400);
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let result = search(&game, 2);
+        assert_eq!(result.best_move, Move::parse("a1a8"));
+        assert_eq!(result.score, MATE - 1);
+        assert!(is_mate_score(result.score));
+    }
+
+    #[test]
+    fn stalemate_is_a_draw() {
+        let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = search(&game, 2);
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn threads_find_the_same_moves() {
+        let options = SearchOptions { depth: 3, threads: 4, ..SearchOptions::default() };
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let result = search_with(&game, &options);
+        assert_eq!(result.best_move, Move::parse("a1a8"));
+        assert_eq!(result.score, MATE - 1);
+
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        assert_eq!(search_with(&game, &options).best_move, Move::parse("d2d5"));
+    }
+
+    #[test]
+    fn one_thread_is_deterministic() {
+        let game = Game::new();
+        let options = SearchOptions { depth: 3, ..SearchOptions::default() };
+        let result = search_with(&game, &options);
+        assert_eq!(result, search_with(&game, &options));
+        assert_eq!(result.pv.len(), 3);
+    }
+
+    #[test]
+    fn engine_reuses_its_table() {
+        let mut engine = Engine::new(SearchOptions { depth: 4, hash_mb: 1, ..SearchOptions::default() });
+        let game = Game::new();
+        let first = engine.search(&game);
+        let second = engine.search(&game);
+        assert_eq!(second.best_move, first.best_move);
+        assert!(second.nodes < first.nodes);
+
+        engine.clear();
+        assert_eq!(engine.search(&game).nodes, first.nodes);
+    }
+
+    #[test]
+    fn multiple_lines() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let result = search_with(&game, &SearchOptions { depth: 3, multi_pv: 3, ..SearchOptions::default() });
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.lines[0].score, Score::Mate(1));
+        assert_eq!(result.lines[0].pv, result.pv);
+        assert_eq!(result.pv[0], Move::parse("a1a8").unwrap());
+        assert!(result.lines[1..].iter().all(|line| line.pv[0] != result.pv[0]));
+
+        // Only as many lines as there are moves
+        let game = Game::from_fen("k7/8/8/2Q5/8/8/8/7K b - - 0 1").unwrap();
+        let result = search_with(&game, &SearchOptions { depth: 2, multi_pv: 5, ..SearchOptions::default() });
+        assert_eq!(result.lines.len(), 2);
+    }
+
+    #[test]
+    fn scores_for_people() {
+        assert_eq!(Score::from_search(MATE - 5), Score::Mate(3));
+        assert_eq!(Score::from_search(-MATE + 4), Score::Mate(-2));
+        assert_eq!(Score::from_search(-35), Score::Centipawns(-35));
+        assert_eq!(Score::Mate(3).to_string(), "mate in 3");
+        assert_eq!(Score::Mate(-2).to_string(), "mated in 2");
+        assert_eq!(Score::Centipawns(35).to_string(), "+0.35");
+        assert_eq!(Score::Centipawns(-120).to_string(), "-1.20");
+    }
+
+    #[test]
+    fn node_limit_stops_deepening() {
+        let game = Game::new();
+        let full = search_with(&game, &SearchOptions { depth: 4, ..SearchOptions::default() });
+        let limited = search_with(&game, &SearchOptions { depth: 4, max_nodes: Some(100), ..SearchOptions::default() });
+        assert!(limited.best_move.is_some());
+        assert!(limited.nodes < full.nodes);
+        assert!(limited.depth < 4 && full.depth == 4);
+    }
+
+    #[test]
+    fn time_limit_stops_deepening() {
+        let start = Instant::now();
+        let result = search_with(&Game::new(), &SearchOptions { depth: 64, max_time: Some(Duration::from_millis(50)), ..SearchOptions::default() });
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(result.best_move.is_some());
+        assert!((1..64).contains(&result.depth));
+    }
+
+    #[test]
+    fn style_weights() {
+        // The white knight is next to the black king and far from its own
+        let game = Game::from_fen("k7/2N5/8/8/8/8/8/7K w - - 0 1").unwrap();
+        assert!(evaluate_with(&game, &Style::aggressive()) > evaluate_with(&game, &Style::defensive()));
+        assert_eq!(evaluate_with(&game, &Style::default()), evaluate(&game));
+
+        let game = Game::from_fen("k7/8/8/8/8/8/5N2/7K w - - 0 1").unwrap();
+        assert!(evaluate_with(&game, &Style::aggressive()) < evaluate_with(&game, &Style::defensive()));
+    }
+}
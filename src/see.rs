@@ -0,0 +1,106 @@
+//! Static exchange evaluation.
+//!
+//! Plays out all captures on one square, each side capturing with its least
+//! valuable piece and stopping when continuing would lose material. Pieces
+//! behind a slider join in once the slider has captured, since attackers are
+//! looked up again after every capture. Pins are not taken into account.
+
+use crate::attacks::attackers;
+use crate::{Colour, Game, Move, Piece, PieceType};
+
+impl PieceType {
+    /// Material value in centipawns.
+    pub fn value(&self) -> i32 {
+        match self {
+            PieceType::PAWN => 100,
+            PieceType::KNIGHT => 320,
+            PieceType::BISHOP => 330,
+            PieceType::ROOK => 500,
+            PieceType::QUEEN => 900,
+            PieceType::KING => 20000,
+        }
+    }
+}
+
+/// Position of the least valuable piece of `colour` attacking `square`.
+fn least_valuable_attacker(board: &[Vec<Option<Piece>>], square: &[usize], colour: Colour) -> Option<Vec<usize>> {
+    attackers(board, square).into_iter()
+        .filter(|position| board[position[0]][position[1]].is_some_and(|piece| piece.color == colour))
+        .min_by_key(|position| board[position[0]][position[1]].unwrap().piece_type.value())
+}
+
+impl Game {
+    /// Net material won (positive) or lost (negative) by the side making `m`
+    /// once the exchange on the target square is over, in centipawns.
+    pub fn see(&self, m: &Move) -> i32 {
+        let Some(piece) = self.board[m.from[0]][m.from[1]] else { return 0 };
+        let mut board = self.board.clone();
+        let target = &m.to;
+
+        // gains[i] is what the side making capture i has won if the exchange stops after it
+        let mut gains = vec![board[target[0]][target[1]].map_or(0, |captured| captured.piece_type.value())];
+        let mut on_square = piece.piece_type.value();
+        board[target[0]][target[1]] = Some(piece);
+        board[m.from[0]][m.from[1]] = None;
+
+        let mut side = piece.color.opposite();
+        while let Some(position) = least_valuable_attacker(&board, target, side) {
+            let attacker = board[position[0]][position[1]].unwrap();
+            board[position[0]][position[1]] = None;
+            // The king may only capture if the square isn't defended anymore
+            if attacker.piece_type == PieceType::KING && least_valuable_attacker(&board, target, side.opposite()).is_some() {
+                break;
+            }
+            gains.push(on_square - gains[gains.len() - 1]);
+            board[target[0]][target[1]] = Some(attacker);
+            on_square = attacker.piece_type.value();
+            side = side.opposite();
+        }
+
+        // Each side may choose to stop capturing, going backwards from the last capture
+        for i in (1..gains.len()).rev() {
+            gains[i - 1] = -(-gains[i - 1]).max(gains[i]);
+        }
+        gains[0]
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::{Game, Move};
+
+    fn see(fen: &str, m: &str) -> i32 {
+        Game::from_fen(fen).unwrap().see(&Move::parse(m).unwrap())
+    }
+
+    #[test]
+    fn undefended_capture() {
+        assert_eq!(see("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"), 100);
+    }
+
+    #[test]
+    fn defended_capture_loses() {
+        // The pawn on d5 is defended by the e6 pawn
+        assert_eq!(see("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), 100 - 900);
+        // Taking a knight defended by a pawn with a pawn still wins a knight for a pawn
+        assert_eq!(see("4k3/8/4p3/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5"), 320 - 100);
+    }
+
+    #[test]
+    fn x_ray_attackers() {
+        // Rxd5 Rxd5 Rxd5: the second white rook behind the first wins the exchange
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        // With only one white rook it loses the rook for a pawn
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", "d2d5"), 100 - 500);
+    }
+
+    #[test]
+    fn king_cannot_recapture_defended_piece() {
+        // Qxe7 is defended by the bishop, so the king can't take back
+        assert_eq!(see("4k3/4p3/8/8/8/B7/8/4Q1K1 w - - 0 1", "e1e7"), 100);
+    }
+}
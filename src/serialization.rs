@@ -0,0 +1,175 @@
+//! Serde support, enabled with the `serde` feature.
+//!
+//! With the feature on, `Game` and the other types derive `Serialize` and
+//! `Deserialize`, which gives a verbose representation with every field.
+//! The compact representation only keeps the start position, the moves and
+//! the result, and replays the moves when it's read back:
+//!
+//! ```text
+//! {"fen":"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1","moves":["e2e4","e7e5"],"result":null,"result_reason":null}
+//! ```
+//!
+//! The clock and pending draw offers are only kept by the verbose representation.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::notation::FenError;
+use crate::{Game, GameResult, GameState, Move, ResultReason};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompactGame {
+    /// FEN of the start position.
+    pub fen: String,
+    /// Moves in coordinate notation, e.g. `e2e4` or `e7e8n`.
+    pub moves: Vec<String>,
+    pub result: Option<GameResult>,
+    pub result_reason: Option<ResultReason>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompactError {
+    Fen(FenError),
+    /// The move couldn't be read or isn't legal in the position.
+    IllegalMove(String),
+}
+
+impl fmt::Display for CompactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompactError::Fen(error) => write!(f, "invalid fen: {}", error),
+            CompactError::IllegalMove(text) => write!(f, "illegal move: {}", text),
+        }
+    }
+}
+
+impl std::error::Error for CompactError {}
+
+impl From<&Game> for CompactGame {
+    fn from(game: &Game) -> CompactGame {
+        CompactGame {
+            fen: game.start_fen.clone(),
+            moves: game.history.iter().map(|m| m.to_string()).collect(),
+            result: game.result,
+            result_reason: game.result_reason,
+        }
+    }
+}
+
+impl TryFrom<CompactGame> for Game {
+    type Error = CompactError;
+
+    /// Sets up the start position and replays every move through `play_move`.
+    fn try_from(compact: CompactGame) -> Result<Game, CompactError> {
+        let mut game = Game::from_fen(&compact.fen).map_err(CompactError::Fen)?;
+        for text in compact.moves {
+            let legal = match Move::parse(&text) {
+                Some(m) => game.play_move(&m).is_some(),
+                None => false,
+            };
+            if !legal {
+                return Err(CompactError::IllegalMove(text));
+            }
+        }
+
+        if let Some(reason) = compact.result_reason {
+            game.end_game(compact.result, reason);
+        }
+        else if compact.result.is_some() {
+            game.state = GameState::GameOver;
+            game.result = compact.result;
+        }
+        Ok(game)
+    }
+}
+
+/// Compact (de)serialisation of a `Game` field:
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct Record {
+///     #[serde(with = "emriks_chess::serialization::compact")]
+///     game: Game,
+/// }
+/// ```
+pub mod compact {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::CompactGame;
+    use crate::Game;
+
+    pub fn serialize<S: Serializer>(game: &Game, serializer: S) -> Result<S::Ok, S::Error> {
+        CompactGame::from(game).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Game, D::Error> {
+        Game::try_from(CompactGame::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{CompactError, CompactGame};
+    use crate::{Colour, Game, GameResult, Move, ResultReason};
+
+    #[derive(Serialize, Deserialize)]
+    struct Record {
+        #[serde(with = "super::compact")]
+        game: Game,
+    }
+
+    fn played_game() -> Game {
+        let mut game = Game::new();
+        game.make_move(vec![6,4], vec![4,4]);
+        game.make_move(vec![1,4], vec![3,4]);
+        game.make_move(vec![7,6], vec![5,5]);
+        game
+    }
+
+    #[test]
+    fn verbose_round_trip() {
+        let game = played_game();
+        let json = serde_json::to_string(&game).unwrap();
+        let read: Game = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.board, game.board);
+        assert_eq!(read.history, game.history);
+        assert_eq!(read.active_colour, Colour::Black);
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        let mut game = played_game();
+        game.resign(Colour::Black);
+        let json = serde_json::to_string(&Record { game }).unwrap();
+        assert!(json.contains(r#""moves":["e2e4","e7e5","g1f3"]"#));
+
+        let read: Record = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.game.to_fen(), played_game().to_fen());
+        assert_eq!(read.game.result, Some(GameResult::Win(Colour::White)));
+        assert_eq!(read.game.result_reason, Some(ResultReason::Resignation));
+    }
+
+    #[test]
+    fn compact_keeps_underpromotion() {
+        let mut game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        game.play_move(&Move::parse("b7b8n").unwrap());
+        let read = Game::try_from(CompactGame::from(&game)).unwrap();
+        assert_eq!(read.to_fen(), "1N2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(read.history, game.history);
+    }
+
+    #[test]
+    fn compact_rejects_illegal_moves() {
+        let mut compact = CompactGame::from(&played_game());
+        compact.moves.push("e5e4".to_string());
+        assert_eq!(Game::try_from(compact).err(), Some(CompactError::IllegalMove("e5e4".to_string())));
+    }
+}
@@ -0,0 +1,498 @@
+//! Server for playing games over the network.
+//!
+//! Clients talk to the server over TCP with one command per line. Every game
+//! has an id, two seats and any number of spectators. All moves go through
+//! `Game::make_move`, and after every change the new state is sent to
+//! everyone in the game.
+//!
+//! Commands:
+//!
+//! ```text
+//! CREATE <id> <seconds> <increment>     create a game with a Fischer clock
+//! JOIN <id> white|black|spectator [token]  join (and create) a game, the token reclaims a seat
+//! MOVE <move>                           make a move in coordinate notation, e.g. e2e4 or e7e8n
+//! RESIGN
+//! DRAW offer|accept|decline
+//! QUIT
+//! ```
+//!
+//! Replies and broadcasts:
+//!
+//! ```text
+//! CREATED <id>
+//! JOINED <id> <role> <token>
+//! STATE <id> <state> <result> <white ms> <black ms> <fen>
+//! OFFER <colour>
+//! LEFT <colour>
+//! ERROR <message>
+//! ```
+//!
+//! The clock of the player to move is running once both seats have been taken.
+//! A seat is kept when its player disconnects, so the player can come back with the token.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::clock::TimeControl;
+use crate::{Colour, Game, GameState, Move};
+
+/// How often the clocks are checked for flag fall.
+const TICK: Duration = Duration::from_millis(50);
+
+/// Longest base time or increment a game can be created with, a week.
+const MAX_TIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+type ClientId = usize;
+
+struct Seat {
+    token: String,
+    client: Option<ClientId>,
+}
+
+struct Room {
+    game: Game,
+    /// White's seat first.
+    seats: [Option<Seat>; 2],
+    spectators: Vec<ClientId>,
+    last_state: GameState,
+    started: bool,
+    turn_started: Instant,
+}
+
+#[derive(Default)]
+struct Lobby {
+    rooms: HashMap<String, Room>,
+    clients: HashMap<ClientId, Sender<String>>,
+    next_client: ClientId,
+}
+
+/// The game and seat a connection has joined.
+struct Membership {
+    room: String,
+    colour: Option<Colour>,
+}
+
+fn seat_index(colour: Colour) -> usize {
+    match colour {
+        Colour::White => 0,
+        Colour::Black => 1,
+    }
+}
+
+fn colour_name(colour: Colour) -> &'static str {
+    match colour {
+        Colour::White => "white",
+        Colour::Black => "black",
+    }
+}
+
+/// Random token for reclaiming a seat.
+fn new_token() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+    format!("{:016x}", hasher.finish())
+}
+
+impl Room {
+    fn new(game: Game) -> Room {
+        Room {
+            game,
+            seats: [None, None],
+            spectators: vec![],
+            last_state: GameState::InProgress,
+            started: false,
+            turn_started: Instant::now(),
+        }
+    }
+
+    fn members(&self) -> Vec<ClientId> {
+        let mut members: Vec<ClientId> = self.seats.iter().flatten().filter_map(|seat| seat.client).collect();
+        members.extend(&self.spectators);
+        members
+    }
+
+    fn state_line(&self, id: &str) -> String {
+        let result = match self.game.result {
+            Some(result) => result.to_string(),
+            None => "*".to_string(),
+        };
+        let (white, black) = match &self.game.clock {
+            Some(clock) => (
+                clock.remaining(Colour::White).as_millis().to_string(),
+                clock.remaining(Colour::Black).as_millis().to_string(),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        // The game only remembers when it's over, check comes from the last move
+        let state = match self.game.get_game_state() {
+            GameState::GameOver => GameState::GameOver,
+            _ => self.last_state,
+        };
+        format!("STATE {} {:?} {} {} {} {}", id, state, result, white, black, self.game.to_fen())
+    }
+}
+
+impl Lobby {
+    fn send(&self, client: ClientId, line: String) {
+        if let Some(sender) = self.clients.get(&client) {
+            let _ = sender.send(line);
+        }
+    }
+
+    fn broadcast(&self, id: &str, line: String) {
+        if let Some(room) = self.rooms.get(id) {
+            for client in room.members() {
+                self.send(client, line.clone());
+            }
+        }
+    }
+
+    fn broadcast_state(&self, id: &str) {
+        if let Some(room) = self.rooms.get(id) {
+            self.broadcast(id, room.state_line(id));
+        }
+    }
+
+    /// Handles one command line from `client`.
+    fn handle(&mut self, client: ClientId, membership: &mut Option<Membership>, line: &str) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["CREATE", id, seconds, increment] => {
+                // Negative, infinite, NaN and huge times are refused instead of panicking with the lobby locked
+                let parse_time = |text: &str| text.parse().ok().and_then(|seconds| Duration::try_from_secs_f64(seconds).ok()).filter(|time| *time <= MAX_TIME);
+                let seconds = parse_time(seconds).ok_or("invalid time")?;
+                let increment = parse_time(increment).ok_or("invalid increment")?;
+                if self.rooms.contains_key(*id) {
+                    return Err("game already exists".to_string());
+                }
+                let mut game = Game::new();
+                game.set_time_control(TimeControl::fischer(seconds, increment));
+                self.rooms.insert(id.to_string(), Room::new(game));
+                self.send(client, format!("CREATED {}", id));
+                Ok(())
+            },
+            ["JOIN", id, role, token @ ..] => {
+                if membership.is_some() {
+                    return Err("already in a game".to_string());
+                }
+                let colour = match *role {
+                    "white" => Some(Colour::White),
+                    "black" => Some(Colour::Black),
+                    "spectator" => None,
+                    _ => return Err("unknown role".to_string()),
+                };
+                let room = self.rooms.entry(id.to_string()).or_insert_with(|| Room::new(Game::new()));
+                let token = match colour {
+                    Some(colour) => {
+                        let seat = &mut room.seats[seat_index(colour)];
+                        match seat {
+                            Some(seat) if token.first() == Some(&seat.token.as_str()) => {
+                                seat.client = Some(client);
+                                seat.token.clone()
+                            },
+                            Some(_) => return Err("seat taken".to_string()),
+                            None => {
+                                let token = new_token();
+                                *seat = Some(Seat { token: token.clone(), client: Some(client) });
+                                token
+                            },
+                        }
+                    },
+                    None => {
+                        room.spectators.push(client);
+                        "-".to_string()
+                    },
+                };
+                if !room.started && room.seats.iter().all(|seat| seat.is_some()) {
+                    room.started = true;
+                    room.turn_started = Instant::now();
+                }
+                let state = room.state_line(id);
+                self.send(client, format!("JOINED {} {} {}", id, role, token));
+                self.send(client, state);
+                *membership = Some(Membership { room: id.to_string(), colour });
+                Ok(())
+            },
+            _ => {
+                let Some(Membership { room: id, colour: Some(colour) }) = membership.as_ref() else {
+                    return Err("not playing".to_string());
+                };
+                let room = self.rooms.get_mut(id).ok_or("no such game")?;
+                match words.as_slice() {
+                    ["MOVE", text] => {
+                        if !room.started {
+                            return Err("waiting for opponent".to_string());
+                        }
+                        if room.game.active_colour != *colour {
+                            return Err("not your turn".to_string());
+                        }
+                        let m = Move::parse(text).ok_or("invalid move")?;
+                        let elapsed = room.turn_started.elapsed();
+                        let state = if room.game.clock.is_some() {
+                            room.game.play_timed_move(&m, elapsed)
+                        }
+                        else {
+                            room.game.play_move(&m)
+                        };
+                        room.last_state = state.ok_or("illegal move")?;
+                        room.turn_started = Instant::now();
+                    },
+                    ["RESIGN"] => {
+                        room.game.resign(*colour);
+                    },
+                    ["DRAW", "offer"] => {
+                        if !room.game.offer_draw(*colour) {
+                            return Err("can't offer a draw".to_string());
+                        }
+                        let line = format!("OFFER {}", colour_name(*colour));
+                        self.broadcast(id, line);
+                        return Ok(());
+                    },
+                    ["DRAW", "accept"] => {
+                        if !room.game.accept_draw(*colour) {
+                            return Err("no draw offer".to_string());
+                        }
+                    },
+                    ["DRAW", "decline"] => {
+                        if !room.game.decline_draw(*colour) {
+                            return Err("no draw offer".to_string());
+                        }
+                    },
+                    _ => return Err("unknown command".to_string()),
+                }
+                self.broadcast_state(id);
+                Ok(())
+            },
+        }
+    }
+
+    /// Removes a closed connection, keeping its seat for a reconnect.
+    fn disconnect(&mut self, client: ClientId, membership: Option<Membership>) {
+        self.clients.remove(&client);
+        let Some(membership) = membership else { return };
+        if let Some(room) = self.rooms.get_mut(&membership.room) {
+            room.spectators.retain(|c| *c != client);
+            if let Some(colour) = membership.colour {
+                if let Some(seat) = &mut room.seats[seat_index(colour)] {
+                    if seat.client == Some(client) {
+                        seat.client = None;
+                    }
+                }
+                self.broadcast(&membership.room, format!("LEFT {}", colour_name(colour)));
+            }
+        }
+    }
+
+    /// Ends games where the player to move has run out of time.
+    fn check_clocks(&mut self) {
+        let mut flagged = vec![];
+        for (id, room) in self.rooms.iter_mut() {
+            if room.started && room.game.clock.is_some() && room.game.get_game_state() != GameState::GameOver
+                && room.game.check_flag(room.turn_started.elapsed()) == GameState::GameOver {
+                flagged.push(id.clone());
+            }
+        }
+        for id in flagged {
+            self.broadcast_state(&id);
+        }
+    }
+}
+
+fn handle_connection(lobby: Arc<Mutex<Lobby>>, stream: TcpStream) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel::<String>();
+    let client = {
+        let mut lobby = lobby.lock().unwrap();
+        let client = lobby.next_client;
+        lobby.next_client += 1;
+        lobby.clients.insert(client, sender);
+        client
+    };
+
+    // Everything sent to the client goes through its own writer thread
+    let mut writer = stream.try_clone()?;
+    thread::spawn(move || {
+        for line in receiver {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut membership = None;
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim() == "QUIT" {
+            break;
+        }
+        let mut lobby = lobby.lock().unwrap();
+        if let Err(message) = lobby.handle(client, &mut membership, &line) {
+            lobby.send(client, format!("ERROR {}", message));
+        }
+    }
+
+    lobby.lock().unwrap().disconnect(client, membership);
+    Ok(())
+}
+
+/// Accepts connections on `listener` and serves games until the listener fails.
+pub fn serve(listener: TcpListener) -> io::Result<()> {
+    let lobby = Arc::new(Mutex::new(Lobby::default()));
+
+    let ticker = Arc::clone(&lobby);
+    thread::spawn(move || loop {
+        thread::sleep(TICK);
+        ticker.lock().unwrap().check_clocks();
+    });
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let lobby = Arc::clone(&lobby);
+        thread::spawn(move || handle_connection(lobby, stream));
+    }
+    Ok(())
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::serve;
+
+    fn start_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener));
+        address
+    }
+
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn connect(address: SocketAddr) -> Client {
+            let stream = TcpStream::connect(address).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            Client { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream }
+        }
+
+        fn send(&mut self, line: &str) {
+            writeln!(self.writer, "{}", line).unwrap();
+        }
+
+        /// Reads lines until one starts with `prefix`.
+        fn expect(&mut self, prefix: &str) -> String {
+            loop {
+                let mut line = String::new();
+                self.reader.read_line(&mut line).unwrap();
+                assert!(!line.is_empty(), "connection closed while waiting for {}", prefix);
+                if line.starts_with(prefix) {
+                    return line.trim_end().to_string();
+                }
+            }
+        }
+
+        /// Joins a game and returns the seat token.
+        fn join(&mut self, id: &str, role: &str) -> String {
+            self.send(&format!("JOIN {} {}", id, role));
+            let joined = self.expect("JOINED");
+            self.expect("STATE");
+            joined.split(' ').nth(3).unwrap().to_string()
+        }
+    }
+
+    #[test]
+    fn moves_are_broadcast() {
+        let address = start_server();
+        let mut white = Client::connect(address);
+        let mut black = Client::connect(address);
+        let mut spectator = Client::connect(address);
+        white.join("g1", "white");
+        black.join("g1", "black");
+        spectator.join("g1", "spectator");
+
+        black.send("MOVE e7e5");
+        assert_eq!(black.expect("ERROR"), "ERROR not your turn");
+        white.send("MOVE e2e5");
+        assert_eq!(white.expect("ERROR"), "ERROR illegal move");
+
+        white.send("MOVE e2e4");
+        let state = "STATE g1 InProgress * - - rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b - - 0 1";
+        assert_eq!(white.expect("STATE"), state);
+        assert_eq!(black.expect("STATE"), state);
+        assert_eq!(spectator.expect("STATE"), state);
+
+        black.send("RESIGN");
+        assert!(spectator.expect("STATE").starts_with("STATE g1 GameOver 1-0"));
+    }
+
+    #[test]
+    fn reconnect_with_token() {
+        let address = start_server();
+        let mut white = Client::connect(address);
+        let mut black = Client::connect(address);
+        let token = white.join("g2", "white");
+        black.join("g2", "black");
+        white.send("QUIT");
+        black.expect("LEFT white");
+
+        let mut intruder = Client::connect(address);
+        intruder.send("JOIN g2 white");
+        assert_eq!(intruder.expect("ERROR"), "ERROR seat taken");
+
+        let mut white = Client::connect(address);
+        white.send(&format!("JOIN g2 white {}", token));
+        white.expect("JOINED g2 white");
+        white.send("MOVE d2d4");
+        black.expect("STATE g2 InProgress");
+    }
+
+    #[test]
+    fn flag_fall_ends_game() {
+        let address = start_server();
+        let mut white = Client::connect(address);
+        let mut black = Client::connect(address);
+        white.send("CREATE g3 0.2 0");
+        white.expect("CREATED g3");
+        white.join("g3", "white");
+        black.join("g3", "black");
+        assert!(black.expect("STATE g3 GameOver").starts_with("STATE g3 GameOver 0-1 0 200"));
+    }
+
+    #[test]
+    fn invalid_time_is_refused() {
+        let address = start_server();
+        let mut client = Client::connect(address);
+        for line in ["CREATE g4 -1 0", "CREATE g4 NaN 0", "CREATE g4 inf 0", "CREATE g4 1e19 0"] {
+            client.send(line);
+            assert_eq!(client.expect("ERROR"), "ERROR invalid time");
+        }
+        for line in ["CREATE g4 60 -1", "CREATE g4 60 1e19"] {
+            client.send(line);
+            assert_eq!(client.expect("ERROR"), "ERROR invalid increment");
+        }
+
+        // The server still answers, on this connection and on new ones
+        client.send("CREATE g4 60 0");
+        client.expect("CREATED g4");
+        Client::connect(address).join("g4", "white");
+    }
+}
@@ -0,0 +1,230 @@
+//! Drawing positions as SVG diagrams.
+//!
+//! Pieces are drawn with the chess symbols of the viewer's font, so the
+//! diagrams don't need any images.
+
+use std::fmt::Write;
+
+use crate::notation::square_name;
+use crate::{Colour, Game, PieceType};
+
+/// Something drawn on top of the board. One with a square off the board is left out.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Annotation {
+    Arrow { from: Vec<usize>, to: Vec<usize>, colour: String },
+    Circle { square: Vec<usize>, colour: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    /// Width of a square in pixels.
+    pub square_size: u32,
+    pub light: String,
+    pub dark: String,
+    /// Files and ranks along the edges.
+    pub coordinates: bool,
+    /// Shows the board from black's side.
+    pub flipped: bool,
+    /// Highlights the squares of the last move in `history`.
+    pub last_move: bool,
+    pub last_move_colour: String,
+    /// Highlights the king of the player to move if it is in check.
+    pub check: bool,
+    pub check_colour: String,
+    pub annotations: Vec<Annotation>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            square_size: 45,
+            light: "#f0d9b5".to_string(),
+            dark: "#b58863".to_string(),
+            coordinates: true,
+            flipped: false,
+            last_move: true,
+            last_move_colour: "#cdd26a".to_string(),
+            check: true,
+            check_colour: "#e05050".to_string(),
+            annotations: vec![],
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn symbol(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::KING => '♚',
+        PieceType::QUEEN => '♛',
+        PieceType::ROOK => '♜',
+        PieceType::BISHOP => '♝',
+        PieceType::KNIGHT => '♞',
+        PieceType::PAWN => '♟',
+    }
+}
+
+impl Game {
+    /// Draws the position as an SVG image.
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let size = options.square_size as f64;
+        let margin = if options.coordinates { size / 2.0 } else { 0.0 };
+        let board_size = size * 8.0;
+        // Top left corner of the square [y, x]
+        let corner = |square: &[usize]| {
+            let (row, column) = if options.flipped { (7 - square[0], 7 - square[1]) } else { (square[0], square[1]) };
+            (margin + column as f64 * size, row as f64 * size)
+        };
+        let centre = |square: &[usize]| {
+            let (x, y) = corner(square);
+            (x + size / 2.0, y + size / 2.0)
+        };
+
+        let mut svg = String::new();
+        let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            margin + board_size, board_size + margin);
+
+        let last_move = self.history.last().filter(|_| options.last_move);
+        let checked_king = match options.check && self.is_in_check(self.active_colour) {
+            true => crate::attacks::find_king(&self.board, self.active_colour),
+            false => None,
+        };
+        for y in 0..8 {
+            for x in 0..8 {
+                let square = vec![y, x];
+                let mut fill = if (y + x) % 2 == 0 { &options.light } else { &options.dark };
+                if last_move.is_some_and(|m| m.from == square || m.to == square) {
+                    fill = &options.last_move_colour;
+                }
+                if checked_king.as_ref() == Some(&square) {
+                    fill = &options.check_colour;
+                }
+                let (left, top) = corner(&square);
+                let _ = writeln!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#, left, top, size, size, escape(fill));
+            }
+        }
+
+        if options.coordinates {
+            for i in 0..8 {
+                let name = square_name(&[i, i]);
+                let (left, top) = corner(&[i, i]);
+                let _ = writeln!(svg, r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle">{}</text>"#,
+                    left + size / 2.0, board_size + margin * 0.75, size / 3.0, &name[0..1]);
+                let _ = writeln!(svg, r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle">{}</text>"#,
+                    margin / 2.0, top + size * 0.6, size / 3.0, &name[1..2]);
+            }
+        }
+
+        for (y, row) in self.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                let Some(piece) = square else { continue };
+                let (fill, stroke) = match piece.color {
+                    Colour::White => ("#ffffff", "#000000"),
+                    Colour::Black => ("#000000", "#ffffff"),
+                };
+                let (cx, cy) = centre(&[y, x]);
+                let _ = writeln!(svg, r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{}" stroke="{}" stroke-width="1">{}</text>"#,
+                    cx, cy, size * 0.8, fill, stroke, symbol(piece.piece_type));
+            }
+        }
+
+        let on_board = |square: &[usize]| square.len() == 2 && square[0] < 8 && square[1] < 8;
+        for annotation in &options.annotations {
+            match annotation {
+                Annotation::Circle { square, .. } if !on_board(square) => {},
+                Annotation::Arrow { from, to, .. } if !on_board(from) || !on_board(to) => {},
+                Annotation::Circle { square, colour } => {
+                    let (cx, cy) = centre(square);
+                    let _ = writeln!(svg, r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="{}" opacity="0.8"/>"#,
+                        cx, cy, size * 0.45, escape(colour), size / 15.0);
+                },
+                Annotation::Arrow { from, to, colour } => {
+                    let (x1, y1) = centre(from);
+                    let (x2, y2) = centre(to);
+                    let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+                    let (dx, dy) = ((x2 - x1) / length, (y2 - y1) / length);
+                    // The shaft ends where the head starts
+                    let head = size / 3.0;
+                    let (bx, by) = (x2 - dx * head, y2 - dy * head);
+                    let (px, py) = (-dy * head / 2.0, dx * head / 2.0);
+                    let _ = writeln!(svg, r#"<g fill="{0}" stroke="{0}" opacity="0.8"><line x1="{1}" y1="{2}" x2="{3}" y2="{4}" stroke-width="{5}"/><polygon points="{6},{7} {8},{9} {10},{11}" stroke="none"/></g>"#,
+                        escape(colour), x1, y1, bx, by, size / 8.0, x2, y2, bx + px, by + py, bx - px, by - py);
+                },
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Annotation, SvgOptions};
+    use crate::Game;
+
+    #[test]
+    fn start_position() {
+        let svg = Game::new().to_svg(&SvgOptions::default());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect").count(), 64);
+        assert_eq!(svg.matches("♟").count(), 16);
+        assert!(svg.contains(">a</text>"));
+        assert!(svg.contains(">8</text>"));
+    }
+
+    #[test]
+    fn highlights_last_move_and_check() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        game.make_move(vec![7, 0], vec![0, 0]);
+        let options = SvgOptions { coordinates: false, ..SvgOptions::default() };
+        let svg = game.to_svg(&options);
+        // a1 and a8 for the move, e8 for the check
+        assert_eq!(svg.matches(&options.last_move_colour).count(), 2);
+        assert!(svg.contains(r##"<rect x="180" y="0" width="45" height="45" fill="#e05050"/>"##));
+    }
+
+    #[test]
+    fn flipped_with_annotations() {
+        let options = SvgOptions {
+            flipped: true,
+            coordinates: false,
+            annotations: vec![
+                Annotation::Arrow { from: vec![6, 4], to: vec![4, 4], colour: "green".to_string() },
+                Annotation::Circle { square: vec![0, 0], colour: "\"red\"".to_string() },
+            ],
+            ..SvgOptions::default()
+        };
+        let svg = Game::new().to_svg(&options);
+        assert!(svg.contains("<polygon"));
+        // a8 is in the bottom right corner
+        assert!(svg.contains(r#"<circle cx="337.5" cy="337.5""#));
+        assert!(svg.contains("&quot;red&quot;"));
+    }
+
+    #[test]
+    fn off_board_annotations_are_left_out() {
+        for flipped in [false, true] {
+            let options = SvgOptions {
+                flipped,
+                annotations: vec![
+                    Annotation::Arrow { from: vec![6, 4], to: vec![8, 4], colour: "green".to_string() },
+                    Annotation::Circle { square: vec![0, 9], colour: "red".to_string() },
+                    Annotation::Circle { square: vec![3], colour: "red".to_string() },
+                    Annotation::Circle { square: vec![0, 0], colour: "blue".to_string() },
+                ],
+                ..SvgOptions::default()
+            };
+            let svg = Game::new().to_svg(&options);
+            assert!(!svg.contains("<polygon"));
+            assert_eq!(svg.matches("<circle").count(), 1);
+        }
+    }
+}
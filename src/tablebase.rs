@@ -0,0 +1,201 @@
+//! Endgame tablebases: perfect results for positions with few pieces.
+//!
+//! A tablebase answers two questions about a position, the result with best
+//! play (`probe_wdl`) and how many half moves it takes until a capture or a
+//! pawn move makes progress towards it (`probe_dtz`), both from the point of
+//! view of the player to move. The names and values follow Syzygy tables,
+//! where a win that takes more than 50 moves to make progress is cursed.
+//!
+//! An `Engine` given a tablebase with `set_tablebase` uses it in the tree,
+//! scoring positions with few enough pieces by their result instead of
+//! searching them, and at the root, where only moves that keep the best
+//! result are searched and a win is made with the fastest progress.
+//!
+//! Reading the Syzygy `.rtbw` and `.rtbz` files themselves isn't done here
+//! yet; a reader plugs in by implementing `Tablebase`.
+
+use crate::search::{ordered_moves, play, MATE_BOUND};
+use crate::{Game, Move, PieceType};
+
+/// Score of a position the tablebase says is won, below any mate.
+pub const TABLEBASE_WIN: i32 = MATE_BOUND - 1000;
+
+/// Win, draw or loss for the player to move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    /// Lost, but drawn by the 50 move rule.
+    BlessedLoss,
+    Draw,
+    /// Won, but drawn by the 50 move rule.
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    /// The result for the other player.
+    pub fn opposite(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+
+    /// Score for the search. Cursed wins and blessed losses are draws, just
+    /// barely better or worse than other draws.
+    pub fn score(self) -> i32 {
+        match self {
+            Wdl::Loss => -TABLEBASE_WIN,
+            Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 1,
+            Wdl::Win => TABLEBASE_WIN,
+        }
+    }
+}
+
+pub trait Tablebase: Send + Sync {
+    /// Positions with more pieces than this, kings included, are never probed.
+    fn max_pieces(&self) -> usize;
+
+    fn probe_wdl(&self, game: &Game) -> Option<Wdl>;
+
+    /// Half moves until the next capture or pawn move with best play,
+    /// positive if the player to move wins, negative if they lose and 0 for a draw.
+    fn probe_dtz(&self, game: &Game) -> Option<i32>;
+}
+
+pub fn piece_count(game: &Game) -> usize {
+    game.board.iter().flatten().filter(|square| square.is_some()).count()
+}
+
+/// The root moves that keep the best result, and of those the ones that make
+/// progress fastest if it is a win, or hold out longest if it is a loss. `None`
+/// if the position isn't in the tablebase.
+pub(crate) fn root_moves(tablebase: &dyn Tablebase, game: &Game) -> Option<Vec<Move>> {
+    if piece_count(game) > tablebase.max_pieces() {
+        return None;
+    }
+    let mut ranked = vec![];
+    for m in ordered_moves(game) {
+        let child = play(game, &m);
+        let wdl = tablebase.probe_wdl(&child)?.opposite();
+        // A capture or pawn move that keeps the result has already made progress
+        let zeroing = game.board[m.to[0]][m.to[1]].is_some()
+            || game.board[m.from[0]][m.from[1]].is_some_and(|piece| piece.piece_type == PieceType::PAWN);
+        let distance = if zeroing { 0 } else { tablebase.probe_dtz(&child)?.abs() };
+        ranked.push((wdl, distance, m));
+    }
+
+    let best = ranked.iter().map(|(wdl, _, _)| *wdl).max()?;
+    ranked.retain(|(wdl, _, _)| *wdl == best);
+    let distance = match best {
+        Wdl::Win | Wdl::CursedWin => ranked.iter().map(|(_, distance, _)| *distance).min(),
+        Wdl::Loss | Wdl::BlessedLoss => ranked.iter().map(|(_, distance, _)| *distance).max(),
+        Wdl::Draw => None,
+    };
+    Some(ranked.into_iter()
+        .filter(|(_, d, _)| distance.is_none_or(|distance| *d == distance))
+        .map(|(_, _, m)| m)
+        .collect())
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{piece_count, Tablebase, Wdl, TABLEBASE_WIN};
+    use crate::search::{is_mate_score, Engine, SearchOptions};
+    use crate::{Game, Move, PieceType};
+
+    /// Every position with three pieces is a draw.
+    struct AllDraws;
+
+    impl Tablebase for AllDraws {
+        fn max_pieces(&self) -> usize {
+            3
+        }
+
+        fn probe_wdl(&self, game: &Game) -> Option<Wdl> {
+            (piece_count(game) <= 3).then_some(Wdl::Draw)
+        }
+
+        fn probe_dtz(&self, game: &Game) -> Option<i32> {
+            (piece_count(game) <= 3).then_some(0)
+        }
+    }
+
+    /// The side with the queen wins, and is closer to it the closer the kings are.
+    struct QueenWins;
+
+    impl Tablebase for QueenWins {
+        fn max_pieces(&self) -> usize {
+            3
+        }
+
+        fn probe_wdl(&self, game: &Game) -> Option<Wdl> {
+            let queen = game.board.iter().flatten().flatten().find(|piece| piece.piece_type == PieceType::QUEEN);
+            Some(match queen {
+                Some(queen) if queen.color == game.active_colour => Wdl::Win,
+                Some(_) => Wdl::Loss,
+                None => Wdl::Draw,
+            })
+        }
+
+        fn probe_dtz(&self, game: &Game) -> Option<i32> {
+            let kings: Vec<(usize, usize)> = (0..8)
+                .flat_map(|y| (0..8).map(move |x| (y, x)))
+                .filter(|&(y, x)| game.board[y][x].is_some_and(|piece| piece.piece_type == PieceType::KING))
+                .collect();
+            let distance = kings[0].0.abs_diff(kings[1].0).max(kings[0].1.abs_diff(kings[1].1)) as i32;
+            Some(match self.probe_wdl(game)? {
+                Wdl::Win => distance,
+                Wdl::Loss => -distance,
+                _ => 0,
+            })
+        }
+    }
+
+    fn engine(depth: u32, tablebase: Option<Box<dyn Tablebase>>) -> Engine {
+        let mut engine = Engine::new(SearchOptions { depth, hash_mb: 1, ..SearchOptions::default() });
+        engine.set_tablebase(tablebase);
+        engine
+    }
+
+    #[test]
+    fn results_for_the_other_player() {
+        assert_eq!(Wdl::Win.opposite(), Wdl::Loss);
+        assert_eq!(Wdl::CursedWin.opposite(), Wdl::BlessedLoss);
+        assert_eq!(Wdl::Draw.opposite(), Wdl::Draw);
+        assert!(Wdl::Win.score() > Wdl::CursedWin.score() && Wdl::CursedWin.score() > Wdl::Draw.score());
+        assert!(!is_mate_score(Wdl::Win.score()));
+    }
+
+    #[test]
+    fn probed_in_the_tree() {
+        // Taking the knight wins material, but the tablebase says the rook ending is a draw
+        let game = Game::from_fen("4k3/8/8/8/7n/8/8/4K2R w - - 0 1").unwrap();
+        let capture = Move::parse("h1h4");
+        assert_eq!(engine(2, None).search(&game).best_move, capture);
+
+        let result = engine(2, Some(Box::new(AllDraws))).search(&game);
+        assert_ne!(result.best_move, capture);
+        assert!(result.score > 0);
+    }
+
+    #[test]
+    fn probed_at_the_root() {
+        // Every move wins, the king walking towards the other king makes the most progress
+        let game = Game::from_fen("k7/8/8/8/8/8/8/4K1Q1 w - - 0 1").unwrap();
+        let result = engine(1, Some(Box::new(QueenWins))).search(&game);
+        let best = result.best_move.unwrap();
+        assert_eq!(best.from, vec![7, 4]);
+        assert_eq!(best.to[0], 6);
+        assert_eq!(result.score, TABLEBASE_WIN);
+    }
+}
@@ -0,0 +1,241 @@
+//! Transposition table shared by search threads.
+//!
+//! Each slot is two atomics, the data and the key XORed with the data, so a
+//! slot written by two threads at once reads as a miss instead of as a mix
+//! of both entries. No locks are taken.
+//!
+//! Slots come in buckets of two. A new entry replaces the one for the same
+//! position, or else the one left by an older search, or else the shallower one.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::{Move, PieceType, PROMOTION_TYPES};
+
+/// How the stored score relates to the real one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The real score is at least the stored one.
+    Lower,
+    /// The real score is at most the stored one.
+    Upper,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+}
+
+fn encode_square(square: &[usize]) -> u64 {
+    (square[0] * 8 + square[1]) as u64
+}
+
+fn decode_square(bits: u64) -> Vec<usize> {
+    vec![(bits / 8) as usize, (bits % 8) as usize]
+}
+
+// 0 for none, else one more than the index in `PROMOTION_TYPES`
+fn encode_promotion(promotion: Option<PieceType>) -> u64 {
+    promotion.and_then(|piece_type| PROMOTION_TYPES.iter().position(|&p| p == piece_type)).map_or(0, |index| index as u64 + 1)
+}
+
+const BUCKET: usize = 2;
+
+fn generation_of(data: u64) -> u8 {
+    (data >> 48) as u8
+}
+
+fn depth_of(data: u64) -> u32 {
+    (data >> 32 & 255) as u32
+}
+
+impl TtEntry {
+    // Bits 0-11 the move, 12 whether there is one, 13-15 the promotion, 16-31 the score, 32-39 the depth,
+    // 40-41 the bound, 48-55 the search that stored it
+    fn pack(&self, generation: u8) -> u64 {
+        let mut data = match &self.best_move {
+            Some(m) => encode_square(&m.from) | encode_square(&m.to) << 6 | 1 << 12 | encode_promotion(m.promotion) << 13,
+            None => 0,
+        };
+        data |= (self.score.clamp(i16::MIN as i32, i16::MAX as i32) as i16 as u16 as u64) << 16;
+        data |= (self.depth.min(255) as u64) << 32;
+        data |= match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        } << 40;
+        data | (generation as u64) << 48
+    }
+
+    fn unpack(data: u64) -> TtEntry {
+        TtEntry {
+            best_move: (data & 1 << 12 != 0).then(|| Move {
+                from: decode_square(data & 63),
+                to: decode_square(data >> 6 & 63),
+                promotion: (data >> 13 & 7).checked_sub(1).map(|index| PROMOTION_TYPES[index as usize]),
+            }),
+            score: (data >> 16) as u16 as i16 as i32,
+            depth: depth_of(data),
+            bound: match data >> 40 & 3 {
+                0 => Bound::Exact,
+                1 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+        }
+    }
+}
+
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// A table with room for about `entries` positions.
+    pub fn new(entries: usize) -> TranspositionTable {
+        let buckets = entries.div_ceil(BUCKET).max(1);
+        TranspositionTable {
+            slots: (0..buckets * BUCKET).map(|_| Slot { check: AtomicU64::new(0), data: AtomicU64::new(0) }).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// A table taking `megabytes` of memory.
+    pub fn with_size_mb(megabytes: usize) -> TranspositionTable {
+        TranspositionTable::new(megabytes * 1024 * 1024 / std::mem::size_of::<Slot>())
+    }
+
+    /// Number of positions the table has room for.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Empties the table, for a new game.
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot.check.get_mut() = 0;
+            *slot.data.get_mut() = 0;
+        }
+        *self.generation.get_mut() = 0;
+    }
+
+    /// Marks the entries stored so far as old, so they are replaced first.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: u64) -> &[Slot] {
+        let start = (key % (self.slots.len() / BUCKET) as u64) as usize * BUCKET;
+        &self.slots[start..start + BUCKET]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        self.bucket(key).iter().find_map(|slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+            (data != 0 && slot.check.load(Ordering::Relaxed) ^ data == key).then(|| TtEntry::unpack(data))
+        })
+    }
+
+    pub fn store(&self, key: u64, entry: &TtEntry) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+        // The slot that is least worth keeping is replaced
+        let keep = |slot: &Slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+            match data {
+                0 => (true, false, 0),
+                _ => (slot.check.load(Ordering::Relaxed) ^ data != key, generation_of(data) == generation, depth_of(data) + 1),
+            }
+        };
+        let slot = bucket.iter().min_by_key(|slot| keep(slot)).unwrap();
+        let data = entry.pack(generation);
+        slot.data.store(data, Ordering::Relaxed);
+        slot.check.store(key ^ data, Ordering::Relaxed);
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Bound, TranspositionTable, TtEntry};
+    use crate::Move;
+
+    fn entry(score: i32, depth: u32) -> TtEntry {
+        TtEntry { best_move: None, score, depth, bound: Bound::Exact }
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let table = TranspositionTable::new(1024);
+        let entry = TtEntry { best_move: Move::parse("e2e4"), score: -29_990, depth: 7, bound: Bound::Lower };
+        table.store(12345, &entry);
+        assert_eq!(table.probe(12345), Some(entry));
+        let entry = TtEntry { best_move: Move::parse("a2a1n"), score: 5, depth: 1, bound: Bound::Exact };
+        table.store(12345, &entry);
+        assert_eq!(table.probe(12345), Some(entry));
+        assert_eq!(table.probe(12345 + 1024), None);
+        assert_eq!(table.probe(1), None);
+    }
+
+    #[test]
+    fn replaces_shallow_and_old_entries() {
+        // One bucket, so every key shares it
+        let table = TranspositionTable::new(2);
+        table.store(1, &entry(1, 8));
+        table.store(2, &entry(2, 2));
+        table.store(3, &entry(3, 5));
+        assert_eq!(table.probe(1), Some(entry(1, 8)));
+        assert_eq!(table.probe(2), None);
+        // The same position is always replaced
+        table.store(1, &entry(4, 1));
+        assert_eq!(table.probe(1), Some(entry(4, 1)));
+
+        table.store(1, &entry(1, 8));
+        table.new_search();
+        table.store(3, &entry(3, 5));
+        table.store(4, &entry(4, 1));
+        assert_eq!(table.probe(3), Some(entry(3, 5)));
+        assert_eq!(table.probe(1), None);
+    }
+
+    #[test]
+    fn size_and_clear() {
+        let mut table = TranspositionTable::with_size_mb(1);
+        assert_eq!(table.capacity(), 65536);
+        table.store(7, &entry(7, 3));
+        table.clear();
+        assert_eq!(table.probe(7), None);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let table = TranspositionTable::new(64);
+        std::thread::scope(|scope| {
+            for thread in 0..4u64 {
+                let table = &table;
+                scope.spawn(move || {
+                    for key in 0..1000 {
+                        table.store(key * 4 + thread, &entry(key as i32, 1));
+                    }
+                });
+            }
+        });
+        // Whatever is found is a whole entry
+        for key in 0..4000 {
+            if let Some(entry) = table.probe(key) {
+                assert_eq!(entry.score as u64, key / 4);
+            }
+        }
+    }
+}
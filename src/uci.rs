@@ -0,0 +1,241 @@
+//! A minimal front-end for the Universal Chess Interface, so the engine can be
+//! used from chess GUIs.
+//!
+//! Commands understood:
+//!
+//! ```text
+//! uci
+//! isready
+//! setoption name MultiPV|Hash|Threads value <n>
+//! ucinewgame
+//! position startpos|fen <fen> [moves <move>...]
+//! go [depth <n>] [nodes <n>] [movetime <ms>] [wtime <ms>] [btime <ms>] [winc <ms>] [binc <ms>] [movestogo <n>] [infinite]
+//! quit
+//! ```
+//!
+//! `go` searches until a limit is reached before it answers, so there is no
+//! `stop`. Without a depth the engine's default depth is used, unless a node
+//! or time limit is given. With `wtime` and `btime` the player to move spends
+//! its time divided by the moves to go, 30 if not given, plus half its
+//! increment. `infinite` can't be stopped, so it searches to the default depth.
+//! Every line found is reported as
+//! `info depth <d> multipv <k> score cp <n>|mate <n> nodes <n> pv <moves>`,
+//! followed by `bestmove`.
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::search::{Engine, Score};
+use crate::{Colour, Game, Move};
+
+/// Depth searched when only a node or time limit is given.
+const MAX_DEPTH: u32 = 64;
+/// Moves the remaining time is divided over if `movestogo` isn't given.
+const MOVES_TO_GO: u64 = 30;
+
+/// The score as UCI writes it, mate scores in moves with mated negative.
+fn uci_score(score: Score) -> String {
+    match score {
+        Score::Centipawns(centipawns) => format!("cp {}", centipawns),
+        Score::Mate(moves) => format!("mate {}", moves),
+    }
+}
+
+pub struct Uci {
+    pub engine: Engine,
+    game: Game,
+}
+
+impl Uci {
+    pub fn new() -> Uci {
+        Uci { engine: Engine::default(), game: Game::new() }
+    }
+
+    /// Runs one command and returns the lines to answer with, or `None` for `quit`.
+    /// Unknown commands are ignored, as the protocol asks.
+    pub fn handle(&mut self, line: &str) -> Option<Vec<String>> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let mut reply = vec![];
+        match words.first().copied() {
+            Some("uci") => {
+                reply.push("id name emriks-chess".to_string());
+                reply.push("id author the emriks-chess authors".to_string());
+                reply.push("option name MultiPV type spin default 1 min 1 max 256".to_string());
+                reply.push(format!("option name Hash type spin default {} min 1 max 4096", self.engine.options.hash_mb));
+                reply.push("option name Threads type spin default 1 min 1 max 256".to_string());
+                reply.push("uciok".to_string());
+            },
+            Some("isready") => reply.push("readyok".to_string()),
+            Some("setoption") => self.set_option(&words[1..]),
+            Some("ucinewgame") => {
+                self.engine.clear();
+                self.game = Game::new();
+            },
+            Some("position") => self.set_position(&words[1..]),
+            Some("go") => reply = self.go(&words[1..]),
+            Some("quit") => return None,
+            _ => {},
+        }
+        Some(reply)
+    }
+
+    fn set_option(&mut self, words: &[&str]) {
+        let value_at = words.iter().position(|&word| word == "value");
+        let name = words[..value_at.unwrap_or(words.len())].iter().skip(1).copied().collect::<Vec<_>>().join(" ");
+        let Some(value) = value_at.and_then(|i| words.get(i + 1)).and_then(|value| value.parse::<usize>().ok()) else { return };
+        match name.to_lowercase().as_str() {
+            "multipv" => self.engine.options.multi_pv = value.max(1),
+            "hash" => self.engine.set_hash_size(value.max(1)),
+            "threads" => self.engine.options.threads = value.max(1),
+            _ => {},
+        }
+    }
+
+    /// Moves that can't be read or played are ignored, with the ones after them.
+    fn set_position(&mut self, words: &[&str]) {
+        let moves_at = words.iter().position(|&word| word == "moves").unwrap_or(words.len());
+        let game = match words.first().copied() {
+            Some("startpos") => Some(Game::new()),
+            Some("fen") => Game::from_fen(&words[1..moves_at].join(" ")).ok(),
+            _ => None,
+        };
+        let Some(mut game) = game else { return };
+        for word in words.iter().skip(moves_at + 1) {
+            if Move::parse(word).and_then(|m| game.play_move(&m)).is_none() {
+                break;
+            }
+        }
+        self.game = game;
+    }
+
+    fn go(&mut self, words: &[&str]) -> Vec<String> {
+        let value = |name: &str| words.iter()
+            .position(|&word| word == name)
+            .and_then(|i| words.get(i + 1))
+            .and_then(|value| value.parse::<u64>().ok());
+        let (time, increment) = match self.game.active_colour {
+            Colour::White => (value("wtime"), value("winc")),
+            Colour::Black => (value("btime"), value("binc")),
+        };
+        // A share of the time left, never more than half of it
+        let budget = time.map(|time| (time / value("movestogo").unwrap_or(MOVES_TO_GO).max(1) + increment.unwrap_or(0) / 2).min(time / 2));
+        let max_time = value("movetime").or(budget).map(Duration::from_millis);
+        let max_nodes = value("nodes");
+
+        let defaults = self.engine.options.clone();
+        let limited = max_time.is_some() || max_nodes.is_some();
+        self.engine.options.depth = value("depth").map_or(if limited { MAX_DEPTH } else { defaults.depth }, |depth| depth as u32);
+        self.engine.options.max_time = max_time;
+        self.engine.options.max_nodes = max_nodes;
+        let result = self.engine.search(&self.game);
+        self.engine.options = defaults;
+
+        let mut reply: Vec<String> = result.lines.iter().enumerate()
+            .map(|(i, line)| {
+                let pv: Vec<String> = line.pv.iter().map(Move::to_string).collect();
+                format!("info depth {} multipv {} score {} nodes {} pv {}", result.depth, i + 1, uci_score(line.score), result.nodes, pv.join(" "))
+            })
+            .collect();
+        reply.push(format!("bestmove {}", result.best_move.map_or("0000".to_string(), |m| m.to_string())));
+        reply
+    }
+}
+
+impl Default for Uci {
+    fn default() -> Uci {
+        Uci::new()
+    }
+}
+
+/// Reads commands from `input` until `quit` or the end of it, answering on `output`.
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    let mut uci = Uci::new();
+    for line in input.lines() {
+        let Some(reply) = uci.handle(&line?) else { break };
+        for line in reply {
+            writeln!(output, "{}", line)?;
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{run, Uci};
+
+    fn session(commands: &str) -> Vec<String> {
+        let mut output = vec![];
+        run(commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn handshake() {
+        let lines = session("uci\nisready\nquit\ngo depth 1\n");
+        assert_eq!(lines.last().map(String::as_str), Some("readyok"));
+        assert!(lines.contains(&"uciok".to_string()));
+        assert!(lines.iter().any(|line| line.starts_with("id author ")));
+        assert!(lines.iter().any(|line| line.starts_with("option name MultiPV")));
+    }
+
+    #[test]
+    fn multi_pv_lines() {
+        let lines = session("setoption name MultiPV value 2\nposition fen k7/8/8/2Q5/8/8/8/7K b - - 0 1\ngo depth 2\n");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("info depth 2 multipv 1 score cp "));
+        assert!(lines[1].starts_with("info depth 2 multipv 2 score cp "));
+        assert!(lines[2].starts_with("bestmove "));
+    }
+
+    #[test]
+    fn mate_scores() {
+        let lines = session("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2\n");
+        assert!(lines[0].starts_with("info depth 2 multipv 1 score mate 1 "));
+        assert_eq!(lines[1], "bestmove a1a8");
+
+        // Black's only move walks into mate
+        let lines = session("position fen 7k/8/6K1/8/8/8/8/R7 b - - 0 1\ngo depth 3\n");
+        assert!(lines[0].starts_with("info depth 3 multipv 1 score mate -1 "));
+    }
+
+    #[test]
+    fn time_and_node_limits() {
+        let start = Instant::now();
+        let lines = session("position startpos\ngo movetime 100\n");
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+        let depth: u32 = lines[0].split_whitespace().nth(2).unwrap().parse().unwrap();
+        assert!((1..64).contains(&depth));
+
+        let lines = session("go wtime 1000 btime 1000 winc 0 binc 0\n");
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+        let lines = session("go nodes 200\n");
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+
+        // The limits only last for one search
+        let mut uci = Uci::new();
+        uci.handle("go movetime 10");
+        assert_eq!(uci.engine.options.max_time, None);
+        assert_eq!(uci.engine.options.depth, 4);
+    }
+
+    #[test]
+    fn position_with_moves() {
+        let mut uci = Uci::new();
+        uci.handle("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(uci.game.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b - - 1 2");
+        // An illegal move stops the list
+        uci.handle("position startpos moves e2e4 e2e4 d7d5");
+        assert_eq!(uci.game.history.len(), 1);
+        assert_eq!(uci.handle("setoption name Hash value 1"), Some(vec![]));
+        assert_eq!(uci.engine.options.hash_mb, 1);
+        assert_eq!(uci.handle("quit"), None);
+    }
+}
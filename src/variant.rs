@@ -0,0 +1,579 @@
+//! Chess variants with other board sizes and fairy pieces.
+//!
+//! `Game` is kept for standard chess. A `Variant` describes the board and its
+//! pieces, and `VariantGame` plays it. Pieces are described in Betza's
+//! notation: `N` is a knight, `BN` an archbishop, `fmWfcFifmnD` a pawn.
+//!
+//! Supported atoms are `W F D N A H C Z G`, with the shorthands `K`
+//! (`WF`), `R` (`WW`), `B` (`FF`) and `Q` (`WWFF`). Doubling an atom
+//! (`WW`) makes it a rider and a number after it (`W3`) limits how far it
+//! rides. The modifiers are `m` (move only), `c` (capture only), `i` (only
+//! the piece's first move), `n` (can't jump over pieces) and the directions
+//! `f b v l r s`. A lame oblique leap goes straight along its longer side
+//! first and then diagonally, so `nN` is the xiangqi horse. There is no
+//! castling or en passant, and pawns promote on the last rank only.
+//!
+//! `VariantGame` is separate from `Game` because everything built on `Game`
+//! assumes its 8x8 board and six piece types, from FEN and SAN to the
+//! search and the Zobrist keys. So a variant game has none of the clock,
+//! events, undo, serde support or SAN and PGN of `Game`, and the engine
+//! can't play it.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Colour, GameResult, GameState, Move};
+
+#[derive(Debug, PartialEq)]
+pub enum BetzaError {
+    UnknownAtom(char),
+    UnknownModifier(char),
+    /// Modifiers at the end with no atom after them.
+    MissingAtom,
+}
+
+impl fmt::Display for BetzaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BetzaError::UnknownAtom(c) => write!(f, "unknown atom {}", c),
+            BetzaError::UnknownModifier(c) => write!(f, "unknown modifier {}", c),
+            BetzaError::MissingAtom => write!(f, "missing atom"),
+        }
+    }
+}
+
+impl std::error::Error for BetzaError {}
+
+/// One way a piece can move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Movement {
+    /// The leap, in squares along one axis and the other.
+    pub leap: (usize, usize),
+    /// How many leaps it may make in a line, None if as many as fit on the board.
+    pub range: Option<usize>,
+    pub can_move: bool,
+    pub can_capture: bool,
+    /// Only allowed as the piece's first move.
+    pub initial: bool,
+    /// The squares in between have to be empty.
+    pub lame: bool,
+    /// Direction modifiers, empty for all directions.
+    pub directions: Vec<char>,
+}
+
+impl Movement {
+    /// The leaps of this movement as [dy, dx] for `colour`.
+    fn offsets(&self, colour: Colour) -> Vec<(i32, i32)> {
+        let (a, b) = (self.leap.0 as i32, self.leap.1 as i32);
+        let mut offsets = vec![];
+        for (forward, right) in [(a, b), (b, a)] {
+            for (forward, right) in [(forward, right), (-forward, right), (forward, -right), (-forward, -right)] {
+                if !offsets.contains(&(forward, right)) && self.goes(forward, right) {
+                    offsets.push((forward, right));
+                }
+            }
+        }
+        offsets.into_iter()
+            .map(|(forward, right)| match colour {
+                Colour::White => (-forward, right),
+                Colour::Black => (forward, -right),
+            })
+            .collect()
+    }
+
+    /// Whether the direction modifiers allow the leap, seen from the piece's side.
+    fn goes(&self, forward: i32, right: i32) -> bool {
+        let vertical: Vec<char> = self.directions.iter().copied().filter(|c| "fbv".contains(*c)).collect();
+        let sideways: Vec<char> = self.directions.iter().copied().filter(|c| "lrs".contains(*c)).collect();
+        let vertical_ok = vertical.is_empty() || vertical.iter().any(|c| match c {
+            'f' => forward > 0,
+            'b' => forward < 0,
+            _ => forward != 0 && forward.abs() >= right.abs(),
+        });
+        let sideways_ok = sideways.is_empty() || sideways.iter().any(|c| match c {
+            'l' => right < 0,
+            'r' => right > 0,
+            _ => right != 0 && right.abs() >= forward.abs(),
+        });
+        vertical_ok && sideways_ok
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceDef {
+    pub name: String,
+    /// Uppercase letter for the piece, lowercase is used for black.
+    pub letter: char,
+    pub movements: Vec<Movement>,
+    /// The player loses when this piece is mated.
+    pub royal: bool,
+    /// Promotes on reaching the last rank.
+    pub promotes: bool,
+}
+
+/// Leaps and how far they ride.
+type Leaps = Vec<((usize, usize), Option<usize>)>;
+
+/// Leaps of a Betza atom, with the range given by the atom itself.
+fn atom(c: char) -> Option<Leaps> {
+    let leap = match c {
+        'W' => (1, 0),
+        'F' => (1, 1),
+        'D' => (2, 0),
+        'N' => (2, 1),
+        'A' => (2, 2),
+        'H' => (3, 0),
+        'C' => (3, 1),
+        'Z' => (3, 2),
+        'G' => (3, 3),
+        'K' => return Some(vec![((1, 0), Some(1)), ((1, 1), Some(1))]),
+        'R' => return Some(vec![((1, 0), None)]),
+        'B' => return Some(vec![((1, 1), None)]),
+        'Q' => return Some(vec![((1, 0), None), ((1, 1), None)]),
+        _ => return None,
+    };
+    Some(vec![(leap, Some(1))])
+}
+
+impl PieceDef {
+    /// Reads a piece described in Betza's notation.
+    pub fn new(name: &str, letter: char, betza: &str) -> Result<PieceDef, BetzaError> {
+        let mut movements = vec![];
+        let mut chars = betza.chars().peekable();
+        let mut modifiers = vec![];
+        while let Some(c) = chars.next() {
+            if c.is_ascii_lowercase() {
+                if !"mcinfbvlrs".contains(c) {
+                    return Err(BetzaError::UnknownModifier(c));
+                }
+                modifiers.push(c);
+                continue;
+            }
+
+            let leaps = atom(c).ok_or(BetzaError::UnknownAtom(c))?;
+            // A doubled atom rides, a number limits the ride
+            let mut rides = None;
+            if chars.peek() == Some(&c) {
+                chars.next();
+                rides = Some(None);
+            }
+            else if chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                let mut number = 0;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    number = number * 10 + digit as usize;
+                    chars.next();
+                }
+                rides = Some(if number == 0 { None } else { Some(number) });
+            }
+
+            let only_moves = modifiers.contains(&'m') && !modifiers.contains(&'c');
+            let only_captures = modifiers.contains(&'c') && !modifiers.contains(&'m');
+            for (leap, range) in leaps {
+                movements.push(Movement {
+                    leap,
+                    range: rides.unwrap_or(range),
+                    can_move: !only_captures,
+                    can_capture: !only_moves,
+                    initial: modifiers.contains(&'i'),
+                    lame: modifiers.contains(&'n'),
+                    directions: modifiers.iter().copied().filter(|c| "fbvlrs".contains(*c)).collect(),
+                });
+            }
+            modifiers.clear();
+        }
+        if !modifiers.is_empty() {
+            return Err(BetzaError::MissingAtom);
+        }
+        Ok(PieceDef { name: name.to_string(), letter, movements, royal: false, promotes: false })
+    }
+
+    fn standard_set() -> Vec<PieceDef> {
+        let piece = |name, letter, betza| PieceDef::new(name, letter, betza).unwrap();
+        vec![
+            PieceDef { promotes: true, ..piece("pawn", 'P', "fmWfcFifmnD") },
+            piece("knight", 'N', "N"),
+            piece("bishop", 'B', "B"),
+            piece("rook", 'R', "R"),
+            piece("queen", 'Q', "Q"),
+            PieceDef { royal: true, ..piece("king", 'K', "K") },
+        ]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub pieces: Vec<PieceDef>,
+    /// Piece placement like the first field of a FEN, starting with black's side.
+    pub setup: String,
+    /// Letters of the pieces a pawn may become, the first is the default.
+    pub promotion_choices: Vec<char>,
+}
+
+impl Variant {
+    pub fn standard() -> Variant {
+        Variant {
+            name: "standard".to_string(),
+            width: 8,
+            height: 8,
+            pieces: PieceDef::standard_set(),
+            setup: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR".to_string(),
+            promotion_choices: vec!['Q', 'R', 'B', 'N'],
+        }
+    }
+
+    /// 10x8 with an archbishop (bishop and knight) and a chancellor (rook and knight).
+    pub fn capablanca() -> Variant {
+        let mut pieces = PieceDef::standard_set();
+        pieces.push(PieceDef::new("archbishop", 'A', "BN").unwrap());
+        pieces.push(PieceDef::new("chancellor", 'C', "RN").unwrap());
+        Variant {
+            name: "capablanca".to_string(),
+            width: 10,
+            height: 8,
+            pieces,
+            setup: "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR".to_string(),
+            promotion_choices: vec!['Q', 'C', 'A', 'R', 'B', 'N'],
+        }
+    }
+
+    /// 10x10 with a cardinal (bishop and knight) and a marshal (rook and knight).
+    pub fn grand() -> Variant {
+        let mut pieces = PieceDef::standard_set();
+        pieces.push(PieceDef::new("cardinal", 'C', "BN").unwrap());
+        pieces.push(PieceDef::new("marshal", 'M', "RN").unwrap());
+        Variant {
+            name: "grand".to_string(),
+            width: 10,
+            height: 10,
+            pieces,
+            setup: "r8r/1nbqkmcbn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R".to_string(),
+            promotion_choices: vec!['Q', 'M', 'C', 'R', 'B', 'N'],
+        }
+    }
+
+    /// Index in `pieces` of the piece with the given uppercase letter.
+    pub fn piece_index(&self, letter: char) -> Option<usize> {
+        self.pieces.iter().position(|piece| piece.letter == letter)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VariantPiece {
+    /// Index in the variant's `pieces`.
+    pub kind: usize,
+    pub colour: Colour,
+    pub moved: bool,
+}
+
+#[derive(Clone)]
+pub struct VariantGame {
+    pub variant: Arc<Variant>,
+    pub board: Vec<Vec<Option<VariantPiece>>>,
+    pub active_colour: Colour,
+    pub state: GameState,
+    pub result: Option<GameResult>,
+    pub history: Vec<Move>,
+    /// Index in the variant's `pieces` of what pawns become.
+    pub promotion: usize,
+}
+
+impl VariantGame {
+    /// Starts a game from the variant's setup. Returns None if the setup doesn't fit the board.
+    pub fn new(variant: Variant) -> Option<VariantGame> {
+        let setup = variant.setup.clone();
+        VariantGame::from_placement(variant, &setup, Colour::White)
+    }
+
+    /// Starts a game from a placement like the first field of a FEN.
+    pub fn from_placement(variant: Variant, placement: &str, active_colour: Colour) -> Option<VariantGame> {
+        let mut board = vec![];
+        for rank in placement.split('/') {
+            let mut row = vec![];
+            let mut empty = 0;
+            for c in rank.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    empty = empty * 10 + digit as usize;
+                    continue;
+                }
+                row.extend(std::iter::repeat_n(None, empty));
+                empty = 0;
+                let colour = if c.is_ascii_uppercase() { Colour::White } else { Colour::Black };
+                let kind = variant.piece_index(c.to_ascii_uppercase())?;
+                row.push(Some(VariantPiece { kind, colour, moved: false }));
+            }
+            row.extend(std::iter::repeat_n(None, empty));
+            if row.len() != variant.width {
+                return None;
+            }
+            board.push(row);
+        }
+        if board.len() != variant.height {
+            return None;
+        }
+
+        let promotion = variant.promotion_choices.first().and_then(|letter| variant.piece_index(*letter)).unwrap_or(0);
+        Some(VariantGame {
+            variant: Arc::new(variant),
+            board,
+            active_colour,
+            state: GameState::InProgress,
+            result: None,
+            history: vec![],
+            promotion,
+        })
+    }
+
+    /// The piece placement like the first field of a FEN.
+    pub fn placement(&self) -> String {
+        let ranks: Vec<String> = self.board.iter().map(|row| {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for square in row {
+                match square {
+                    Some(piece) => {
+                        if empty > 0 {
+                            rank += &empty.to_string();
+                            empty = 0;
+                        }
+                        let letter = self.variant.pieces[piece.kind].letter;
+                        rank.push(if piece.colour == Colour::White { letter } else { letter.to_ascii_lowercase() });
+                    },
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                rank += &empty.to_string();
+            }
+            rank
+        }).collect();
+        ranks.join("/")
+    }
+
+    /// Sets what pawns are promoted to. Returns false if it isn't one of the variant's choices.
+    pub fn set_promotion(&mut self, letter: char) -> bool {
+        match self.variant.piece_index(letter) {
+            Some(kind) if self.variant.promotion_choices.contains(&letter) => {
+                self.promotion = kind;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn square(&self, position: &[usize], (dy, dx): (i32, i32), steps: i32) -> Option<Vec<usize>> {
+        let y = position[0] as i32 + dy * steps;
+        let x = position[1] as i32 + dx * steps;
+        if y < 0 || x < 0 || y >= self.variant.height as i32 || x >= self.variant.width as i32 {
+            return None;
+        }
+        Some(vec![y as usize, x as usize])
+    }
+
+    /// Whether the squares one lame leap passes over are empty. It goes
+    /// straight for the difference of its sides and then diagonally.
+    fn path_is_clear(&self, from: &[usize], (dy, dx): (i32, i32)) -> bool {
+        let (long, short) = (dy.abs().max(dx.abs()), dy.abs().min(dx.abs()));
+        let straight = if dy.abs() >= dx.abs() { (dy.signum(), 0) } else { (0, dx.signum()) };
+        let diagonal = (dy.signum(), dx.signum());
+        let (mut y, mut x) = (from[0] as i32, from[1] as i32);
+        // The last step lands on the square leapt to
+        (0..long - 1).all(|step| {
+            let (sy, sx) = if step < long - short { straight } else { diagonal };
+            y += sy;
+            x += sx;
+            self.board[y as usize][x as usize].is_none()
+        })
+    }
+
+    /// Squares the piece on `from` can go to, ignoring whether its own royal piece is left attacked.
+    fn reachable(&self, from: &[usize]) -> Vec<Vec<usize>> {
+        let Some(piece) = self.board[from[0]][from[1]] else { return vec![] };
+        let mut squares = vec![];
+        for movement in &self.variant.pieces[piece.kind].movements {
+            if movement.initial && piece.moved {
+                continue;
+            }
+            for offset in movement.offsets(piece.colour) {
+                let mut steps = 1;
+                while let Some(to) = self.square(from, offset, steps) {
+                    // The leaps before this one landed on empty squares
+                    let start = self.square(from, offset, steps - 1).unwrap();
+                    if movement.lame && !self.path_is_clear(&start, offset) {
+                        break;
+                    }
+                    match self.board[to[0]][to[1]] {
+                        None if movement.can_move => squares.push(to.clone()),
+                        Some(other) if other.colour != piece.colour && movement.can_capture => squares.push(to.clone()),
+                        _ => {},
+                    }
+                    if self.board[to[0]][to[1]].is_some() || movement.range.is_some_and(|range| steps >= range as i32) {
+                        break;
+                    }
+                    steps += 1;
+                }
+            }
+        }
+        squares
+    }
+
+    /// Whether a piece of `by` could capture on `square`.
+    pub fn is_attacked(&self, square: &[usize], by: Colour) -> bool {
+        let mut board = self.clone();
+        // Capture-only moves need something to capture
+        board.board[square[0]][square[1]] = Some(VariantPiece { kind: 0, colour: by.opposite(), moved: true });
+        (0..self.variant.height).any(|y| (0..self.variant.width).any(|x| {
+            board.board[y][x].is_some_and(|piece| piece.colour == by) && board.reachable(&[y, x]).iter().any(|to| to == square)
+        }))
+    }
+
+    /// Whether one of `colour`'s royal pieces is attacked.
+    pub fn is_in_check(&self, colour: Colour) -> bool {
+        (0..self.variant.height).any(|y| (0..self.variant.width).any(|x| {
+            self.board[y][x].is_some_and(|piece| piece.colour == colour && self.variant.pieces[piece.kind].royal)
+                && self.is_attacked(&[y, x], colour.opposite())
+        }))
+    }
+
+    /// Moves the piece and promotes it, without checking anything.
+    fn apply(&mut self, m: &Move) {
+        let mut piece = self.board[m.from[0]][m.from[1]].take().unwrap();
+        piece.moved = true;
+        let last_rank = match piece.colour {
+            Colour::White => 0,
+            Colour::Black => self.variant.height - 1,
+        };
+        if self.variant.pieces[piece.kind].promotes && m.to[0] == last_rank {
+            piece.kind = self.promotion;
+        }
+        self.board[m.to[0]][m.to[1]] = Some(piece);
+        self.active_colour = piece.colour.opposite();
+    }
+
+    /// Legal moves of the player to move.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = vec![];
+        for y in 0..self.variant.height {
+            for x in 0..self.variant.width {
+                if !self.board[y][x].is_some_and(|piece| piece.colour == self.active_colour) {
+                    continue;
+                }
+                for to in self.reachable(&[y, x]) {
+                    let m = Move::new(vec![y, x], to);
+                    let mut after = self.clone();
+                    after.apply(&m);
+                    if !after.is_in_check(self.active_colour) {
+                        moves.push(m);
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    /// Makes the move if it is legal and returns the state of the game after it.
+    pub fn make_move(&mut self, from: Vec<usize>, to: Vec<usize>) -> Option<GameState> {
+        if self.state == GameState::GameOver {
+            return Some(GameState::GameOver);
+        }
+        let m = Move::new(from, to);
+        if !self.legal_moves().contains(&m) {
+            return None;
+        }
+        self.apply(&m);
+        self.history.push(m);
+
+        let in_check = self.is_in_check(self.active_colour);
+        if self.legal_moves().is_empty() {
+            self.state = GameState::GameOver;
+            self.result = Some(if in_check { GameResult::Win(self.active_colour.opposite()) } else { GameResult::Draw });
+            return Some(GameState::GameOver);
+        }
+        Some(if in_check { GameState::Check } else { GameState::InProgress })
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{BetzaError, PieceDef, Variant, VariantGame};
+    use crate::{Colour, GameResult, GameState};
+
+    #[test]
+    fn parse_betza() {
+        let archbishop = PieceDef::new("archbishop", 'A', "BN").unwrap();
+        assert_eq!(archbishop.movements.len(), 2);
+        assert_eq!(archbishop.movements[0].range, None);
+        assert_eq!(archbishop.movements[1].leap, (2, 1));
+
+        let short_rook = PieceDef::new("short rook", 'S', "W3").unwrap();
+        assert_eq!(short_rook.movements[0].range, Some(3));
+
+        assert_eq!(PieceDef::new("x", 'X', "X"), Err(BetzaError::UnknownAtom('X')));
+        assert_eq!(PieceDef::new("x", 'X', "Nq"), Err(BetzaError::UnknownModifier('q')));
+        assert_eq!(PieceDef::new("x", 'X', "Nf"), Err(BetzaError::MissingAtom));
+    }
+
+    #[test]
+    fn standard_variant_moves() {
+        let game = VariantGame::new(Variant::standard()).unwrap();
+        // 16 pawn moves and 4 knight moves
+        assert_eq!(game.legal_moves().len(), 20);
+        assert_eq!(game.placement(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+    }
+
+    #[test]
+    fn capablanca_and_grand() {
+        let mut game = VariantGame::new(Variant::capablanca()).unwrap();
+        // 20 pawn moves and 2 each for the knights, the archbishop and the chancellor
+        assert_eq!(game.legal_moves().len(), 28);
+        game.make_move(vec![6, 2], vec![4, 2]).unwrap();
+        assert_eq!(game.placement(), "rnabqkbcnr/pppppppppp/10/10/2P7/10/PP1PPPPPPP/RNABQKBCNR");
+
+        let game = VariantGame::new(Variant::grand()).unwrap();
+        assert_eq!(game.board.len(), 10);
+        assert!(game.legal_moves().iter().any(|m| m.from == vec![9, 0] && m.to == vec![8, 0]));
+    }
+
+    #[test]
+    fn lame_leaps() {
+        let mut variant = Variant::standard();
+        variant.pieces.push(PieceDef::new("horse", 'H', "nN").unwrap());
+        variant.pieces.push(PieceDef::new("lame dabbaba", 'L', "nDD").unwrap());
+
+        // The pawn in front of the horse blocks its two leaps forward
+        let game = VariantGame::from_placement(variant.clone(), "4k3/8/8/3P4/3H4/8/8/4K3", Colour::White).unwrap();
+        let leaps: Vec<Vec<usize>> = game.legal_moves().into_iter().filter(|m| m.from == vec![4, 3]).map(|m| m.to).collect();
+        assert_eq!(leaps.len(), 6);
+        assert!(!leaps.contains(&vec![2, 2]) && !leaps.contains(&vec![2, 4]));
+        assert!(leaps.contains(&vec![5, 1]));
+
+        // A lame dabbaba rider stops at the first leap over a piece
+        let game = VariantGame::from_placement(variant, "4k3/8/8/8/p7/8/8/L3K3", Colour::White).unwrap();
+        let leaps: Vec<Vec<usize>> = game.legal_moves().into_iter().filter(|m| m.from == vec![7, 0]).map(|m| m.to).collect();
+        assert_eq!(leaps, vec![vec![5, 0], vec![7, 2]]);
+    }
+
+    #[test]
+    fn amazon_mates_and_pawn_promotes() {
+        let mut variant = Variant::standard();
+        variant.pieces.push(PieceDef::new("amazon", 'M', "QN").unwrap());
+        variant.promotion_choices.push('M');
+
+        // The amazon covers every square around the king from a distance a queen couldn't
+        let mut game = VariantGame::from_placement(variant.clone(), "7k/8/8/8/8/8/8/K5M1", Colour::White).unwrap();
+        assert_eq!(game.make_move(vec![7, 6], vec![2, 6]), Some(GameState::GameOver));
+        assert_eq!(game.result, Some(GameResult::Win(Colour::White)));
+
+        let mut game = VariantGame::from_placement(variant, "8/P6k/8/8/8/8/8/K7", Colour::White).unwrap();
+        assert!(game.set_promotion('M'));
+        assert!(!game.set_promotion('K'));
+        game.make_move(vec![1, 0], vec![0, 0]).unwrap();
+        assert_eq!(game.placement(), "M7/7k/8/8/8/8/8/K7");
+    }
+}
@@ -0,0 +1,138 @@
+//! Zobrist hashing of positions.
+//!
+//! The keys are laid out like in the Polyglot book format: 768 piece-square
+//! keys, 4 castling keys, 8 en passant keys and one key for white to move.
+//! `Game` supports neither castling nor en passant, so only the piece and
+//! turn keys are used.
+//!
+//! The default keys are generated, not the Random64 table published with
+//! Polyglot, which isn't bundled yet. Books made by other programs can still
+//! be read by loading that table with `Zobrist::from_keys`; since `Game` has no
+//! castling rights, a position where castling is still allowed then hashes to
+//! the Polyglot key XORed with the castling keys 768 to 771.
+
+use std::sync::OnceLock;
+
+use crate::{Colour, Game, PieceType};
+
+pub const KEY_COUNT: usize = 781;
+const TURN_KEY: usize = 780;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Zobrist {
+    keys: Vec<u64>,
+}
+
+/// Step of the SplitMix64 generator, used wherever a reproducible random number is needed.
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+impl Zobrist {
+    /// Keys generated from a fixed seed, so hashes are the same between runs.
+    pub fn new() -> Zobrist {
+        let mut state = 0x656D72696B73;
+        Zobrist {
+            keys: (0..KEY_COUNT).map(|_| splitmix64(&mut state)).collect(),
+        }
+    }
+
+    /// Uses the given keys, which must be `KEY_COUNT` long and in Polyglot order.
+    pub fn from_keys(keys: &[u64]) -> Option<Zobrist> {
+        if keys.len() != KEY_COUNT {
+            return None;
+        }
+        Some(Zobrist { keys: keys.to_vec() })
+    }
+
+    /// Index of the key for a piece on [y, x].
+    fn piece_key(colour: Colour, piece_type: PieceType, y: usize, x: usize) -> usize {
+        let kind = match piece_type {
+            PieceType::PAWN => 0,
+            PieceType::KNIGHT => 1,
+            PieceType::BISHOP => 2,
+            PieceType::ROOK => 3,
+            PieceType::QUEEN => 4,
+            PieceType::KING => 5,
+        } * 2 + if colour == Colour::White { 1 } else { 0 };
+        // Polyglot counts rows from rank 1, which is y = 7
+        64 * kind + 8 * (7 - y) + x
+    }
+
+    pub fn hash(&self, game: &Game) -> u64 {
+        let mut hash = 0;
+        for (y, row) in game.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                if let Some(piece) = square {
+                    hash ^= self.keys[Zobrist::piece_key(piece.color, piece.piece_type, y, x)];
+                }
+            }
+        }
+        if game.active_colour == Colour::White {
+            hash ^= self.keys[TURN_KEY];
+        }
+        hash
+    }
+}
+
+impl Default for Zobrist {
+    fn default() -> Zobrist {
+        Zobrist::new()
+    }
+}
+
+impl Game {
+    /// Zobrist hash of the position with the default keys.
+    pub fn zobrist_hash(&self) -> u64 {
+        static KEYS: OnceLock<Zobrist> = OnceLock::new();
+        KEYS.get_or_init(Zobrist::new).hash(self)
+    }
+}
+
+// --------------------------
+// ######### TESTS ##########
+// --------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Zobrist, KEY_COUNT};
+    use crate::Game;
+
+    #[test]
+    fn same_position_same_hash() {
+        // Knights out and back again gives the start position with white to move
+        let mut game = Game::new();
+        let start = game.zobrist_hash();
+        game.make_move(vec![7,6], vec![5,5]);
+        assert_ne!(game.zobrist_hash(), start);
+        game.make_move(vec![0,6], vec![2,5]);
+        game.make_move(vec![5,5], vec![7,6]);
+        game.make_move(vec![2,5], vec![0,6]);
+        assert_eq!(game.zobrist_hash(), start);
+    }
+
+    #[test]
+    fn custom_keys() {
+        let mut keys = vec![0; KEY_COUNT];
+        assert!(Zobrist::from_keys(&keys[1..]).is_none());
+        // White pawn on e2 and white to move
+        keys[64 + 8 + 4] = 1;
+        keys[780] = 2;
+        let game = Game::from_fen("8/8/8/8/8/8/4P3/8 w - - 0 1").unwrap();
+        assert_eq!(Zobrist::from_keys(&keys).unwrap().hash(&game), 3);
+    }
+
+    #[test]
+    fn polyglot_layout() {
+        // Every piece of the start position at the index the Polyglot format gives it
+        let keys: Vec<u64> = (0..KEY_COUNT as u64).map(|i| (i + 1).wrapping_mul(0x9E3779B97F4A7C15)).collect();
+        let mut indices: Vec<usize> = (48..56).chain(72..80).collect();
+        indices.extend([185, 190, 193, 198, 314, 317, 322, 325, 440, 447, 448, 455, 571, 579, 700, 708, 780]);
+        let expected = indices.iter().fold(0, |hash, &i| hash ^ keys[i]);
+        assert_eq!(Zobrist::from_keys(&keys).unwrap().hash(&Game::new()), expected);
+    }
+}